@@ -1,11 +1,20 @@
 use std::{
-    sync::atomic::{AtomicU32, Ordering},
-    time::Duration,
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc, Mutex, RwLock,
+    },
+    time::{Duration, Instant},
 };
 
 use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
 use rand::Rng;
-use typedcache::typed::TypedMap;
+use typedcache::{
+    clock::{Clock, MockClock},
+    item::CacheItem,
+    table::CacheTable,
+    typed::TypedMap,
+};
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
 pub struct TestKey(usize);
@@ -13,6 +22,7 @@ pub struct TestKey(usize);
 impl TypedMap for TestKey {
     type Value = TestValue;
 }
+#[allow(dead_code)]
 pub struct TestValue(usize);
 
 async fn not_found_add(num: u32) {
@@ -41,12 +51,96 @@ async fn not_found_add(num: u32) {
     });
 }
 
+/// A table as it was before deadlines were tracked, with the time each item was last accessed.
+struct ScannedTable(RwLock<HashMap<TestKey, (Instant, CacheItem)>>);
+
+impl ScannedTable {
+    /// The expiration check before deadlines were tracked: a scan over every cached item under the lock.
+    fn clean_up(&self, now: Instant) -> Vec<TestKey> {
+        let mut items = self.0.write().unwrap();
+        let expired: Vec<TestKey> = items
+            .iter()
+            .filter(|(_, (accessed_on, item))| {
+                let life_span = item.life_span();
+                life_span > Duration::ZERO && now.duration_since(*accessed_on) >= life_span
+            })
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in &expired {
+            items.remove(key);
+        }
+        expired
+    }
+
+    fn add(&self, now: Instant, key: TestKey) {
+        let item = CacheItem::new(key.clone(), life_span(&key), TestValue(key.0));
+        self.0.write().unwrap().insert(key, (now, item));
+    }
+}
+
+/// Spreads life spans over 1 to 100 seconds, so that a few percent of the items expire every second.
+fn life_span(key: &TestKey) -> Duration {
+    Duration::from_secs(1 + key.0 as u64 % 100)
+}
+
 fn criterion_benchmark(c: &mut Criterion) {
     let num = 10;
     c.bench_with_input(BenchmarkId::new("not_found_add", num), &num, |b, n| {
         b.to_async(tokio::runtime::Runtime::new().unwrap())
             .iter(|| not_found_add(*n));
     });
+
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let _guard = runtime.enter();
+    let mut group = c.benchmark_group("expiration");
+    for size in [1_000, 100_000] {
+        // Every iteration advances the clock by a second, then expires the items which are due and
+        // adds them again, so that the tables keep their size.
+        let clock = MockClock::new();
+        let scanned = ScannedTable(RwLock::new(HashMap::new()));
+        for i in 0..size {
+            scanned.add(clock.now(), TestKey(i));
+        }
+        group.bench_function(BenchmarkId::new("full_scan", size), |b| {
+            b.iter(|| {
+                clock.advance(Duration::from_secs(1));
+                let now = clock.now();
+                for key in scanned.clean_up(now) {
+                    scanned.add(now, key);
+                }
+            });
+        });
+
+        let clock = MockClock::new();
+        let mut table = CacheTable::new(format!("expiration_{}", size));
+        table.set_clock(clock.clone());
+        let expired = Arc::new(Mutex::new(Vec::new()));
+        table.add_about_to_delete_item_callback({
+            let expired = expired.clone();
+            move |item, _| {
+                if let Some(key) = item.key().downcast_ref::<TestKey>() {
+                    expired.lock().unwrap().push(key.clone());
+                }
+            }
+        });
+        for i in 0..size {
+            let key = TestKey(i);
+            table.add(key.clone(), life_span(&key), TestValue(i));
+        }
+        group.bench_function(BenchmarkId::new("deadlines", size), |b| {
+            b.iter(|| {
+                clock.advance(Duration::from_secs(1));
+                table.clean_up();
+                let keys = std::mem::take(&mut *expired.lock().unwrap());
+                for key in keys {
+                    let life_span = life_span(&key);
+                    let value = TestValue(key.0);
+                    table.add(key, life_span, value);
+                }
+            });
+        });
+    }
+    group.finish();
 }
 
 criterion_group!(benches, criterion_benchmark);
@@ -21,7 +21,10 @@ async fn main() {
     assert!(cache.get(&test_key_expired_after_2s).is_some());
     tokio::time::sleep(Duration::from_secs(1)).await;
     assert!(cache.get(&test_key_expired_after_2s).is_none());
-    assert!(cache.get(&test_key_never_expired).is_some());
+    assert_eq!(
+        cache.get_value(&test_key_never_expired).map(|v| v.0),
+        Some(0)
+    );
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
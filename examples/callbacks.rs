@@ -11,13 +11,16 @@ async fn main() {
     cache.add_added_item_callback(|cache_item| {
         println!("Added Callback 2: {:?}", cache_item.created_on());
     });
-    cache.set_about_to_delete_item_callback(|cache_item| {
-        println!("Deleting: {:?}", cache_item.created_on());
+    cache.set_about_to_delete_item_callback(|cache_item, cause| {
+        println!("Deleting ({:?}): {:?}", cause, cache_item.created_on());
     });
 
     let test_key = TestKey("test".into());
     cache.add(test_key.clone(), Duration::from_secs(0), TestValue(0));
     assert!(cache.value(test_key.clone()).is_ok());
+    if let Some(value) = cache.get_value(&test_key) {
+        println!("Value: {}", value.0);
+    }
 
     _ = cache.delete(&test_key);
 
@@ -25,9 +28,10 @@ async fn main() {
     cache.add(test_key.clone(), Duration::from_secs(3), TestValue(0));
 
     let item = cache.value(test_key.clone()).unwrap();
-    item.set_about_to_expire_callback(Box::new(|key: &TypedKey| {
+    item.set_about_to_expire_callback(Box::new(|key: &TypedKey, cause| {
         println!(
-            "About to expire: {:?}",
+            "About to expire ({:?}): {:?}",
+            cause,
             key.downcast_ref::<TestKey>().unwrap()
         );
     }));
@@ -1,26 +1,19 @@
 use std::time::Duration;
 
-use typedcache::{
-    item::CacheItem,
-    typed::{typedkey::TypedKey, TypedMap},
-};
+use typedcache::typed::TypedMap;
 
 #[tokio::main]
 async fn main() {
     let mut cache = typedcache::cache("test".into());
-    cache.set_data_loader(|key: TypedKey| {
-        let key = key.downcast_ref::<TestKey>().unwrap();
+    cache.set_loader(|key: &TestKey| {
         let val = TestValue(format!("This is a test with key {}", key.0));
-        Some(CacheItem::new(key.clone(), Duration::from_secs(0), val))
+        Some((val, Duration::from_secs(0)))
     });
     for i in 0..10 {
         let key = TestKey(format!("someKey_{}", i));
-        match cache.value(key) {
-            Ok(val) => {
-                println!(
-                    "Found value in cache: {:?}",
-                    val.value().downcast_ref::<TestValue>().unwrap().clone()
-                );
+        match cache.value(key.clone()) {
+            Ok(_) => {
+                println!("Found value in cache: {:?}", cache.get_value(&key));
             }
             Err(err) => {
                 println!("Error retrieving value from cache: {:?}", err);
@@ -0,0 +1,217 @@
+//! Entry API of a cache table, modeled after [`std::collections::hash_map::Entry`].
+
+use std::{sync::RwLockWriteGuard, time::Duration};
+
+use crate::{
+    item::{CacheItem, RemovalCause},
+    table::{CacheTable, Items, Notifications},
+    typed::{
+        typedkey::{Key, TypedKey, TypedKeyRef},
+        TypedMap,
+    },
+};
+
+/// A view into a single entry of a cache table, which may either be vacant or occupied.
+///
+/// The table is locked for as long as the entry is alive, so every operation on it is atomic.
+pub enum Entry<'a, K: 'static + TypedMap + Send + Sync + Clone>
+where
+    K::Value: Send + Sync,
+{
+    /// An occupied entry.
+    Occupied(OccupiedEntry<'a, K>),
+    /// A vacant entry.
+    Vacant(VacantEntry<'a, K>),
+}
+
+impl<'a, K: 'static + TypedMap + Send + Sync + Clone> Entry<'a, K>
+where
+    K::Value: Send + Sync,
+{
+    /// Returns a reference to this entry's key.
+    pub fn key(&self) -> &K {
+        match self {
+            Entry::Occupied(entry) => entry.key(),
+            Entry::Vacant(entry) => entry.key(),
+        }
+    }
+
+    /// Ensures an item is in the entry by inserting the given value if empty, and returns the item.
+    pub fn or_insert(self, life_span: Duration, value: K::Value) -> CacheItem {
+        match self {
+            Entry::Occupied(entry) => entry.into_item(),
+            Entry::Vacant(entry) => entry.insert(life_span, value),
+        }
+    }
+
+    /// Ensures an item is in the entry by inserting the result of the given function if empty, and returns the item.
+    pub fn or_insert_with(self, life_span: Duration, f: impl FnOnce() -> K::Value) -> CacheItem {
+        match self {
+            Entry::Occupied(entry) => entry.into_item(),
+            Entry::Vacant(entry) => entry.insert(life_span, f()),
+        }
+    }
+
+    /// Mutates the value of an occupied entry in place before any potential inserts.
+    ///
+    /// The item is reweighed and its expiration updated, as by [`CacheTable::update`], which also
+    /// describes when the value is cloned.
+    #[must_use]
+    pub fn and_modify(self, f: impl FnOnce(&mut K::Value)) -> Self
+    where
+        K::Value: Clone,
+    {
+        match self {
+            Entry::Occupied(mut entry) => {
+                entry.modify(f);
+                Entry::Occupied(entry)
+            }
+            Entry::Vacant(entry) => Entry::Vacant(entry),
+        }
+    }
+}
+
+/// A view into an occupied entry of a cache table.
+pub struct OccupiedEntry<'a, K: 'static + TypedMap + Send + Sync + Clone>
+where
+    K::Value: Send + Sync,
+{
+    table: &'a CacheTable,
+    /// Only taken when the entry is dropped, so that the lock is released before notifying.
+    items: Option<RwLockWriteGuard<'a, Items>>,
+    key: K,
+    item: CacheItem,
+    /// The changes made through this entry, if it was modified.
+    notifications: Option<Notifications>,
+}
+
+impl<'a, K: 'static + TypedMap + Send + Sync + Clone> OccupiedEntry<'a, K>
+where
+    K::Value: Send + Sync,
+{
+    pub(crate) fn new(
+        table: &'a CacheTable,
+        items: RwLockWriteGuard<'a, Items>,
+        key: K,
+        item: CacheItem,
+    ) -> Self {
+        Self {
+            table,
+            items: Some(items),
+            key,
+            item,
+            notifications: None,
+        }
+    }
+
+    /// Returns a reference to this entry's key.
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    /// Returns a reference to this entry's item.
+    pub fn get(&self) -> &CacheItem {
+        &self.item
+    }
+
+    /// Converts the entry into its item.
+    pub fn into_item(self) -> CacheItem {
+        self.item.clone()
+    }
+
+    /// Mutates the value of the item in place and recomputes its weight and deadline.
+    fn modify(&mut self, f: impl FnOnce(&mut K::Value))
+    where
+        K::Value: Clone,
+    {
+        self.item.value().update(f);
+        if let Some(items) = self.items.as_mut() {
+            self.table.modified(
+                items,
+                &self.item,
+                self.notifications.get_or_insert_with(Default::default),
+            );
+        }
+    }
+
+    /// Removes the item from the cache and returns it.
+    pub fn remove(mut self) -> CacheItem {
+        if let Some(mut items) = self.items.take() {
+            items.remove(&TypedKeyRef::from_key_ref(&self.key) as &dyn Key);
+        }
+        self.table.counters().record_deletion();
+        tracing::trace!(
+            "Deleting item created on {:?} and hit {} times from table {}",
+            self.item.created_on(),
+            self.item.access_count(),
+            self.table.name()
+        );
+        let mut notifications = self.notifications.take().unwrap_or_default();
+        notifications.removed(self.item.clone(), RemovalCause::Explicit);
+        self.table.dispatch(notifications);
+        self.item.clone()
+    }
+}
+
+impl<K: 'static + TypedMap + Send + Sync + Clone> Drop for OccupiedEntry<'_, K>
+where
+    K::Value: Send + Sync,
+{
+    fn drop(&mut self) {
+        drop(self.items.take());
+        if let Some(notifications) = self.notifications.take() {
+            self.table.wake_clean_up(self.item.expires_at());
+            self.table.dispatch(notifications);
+        }
+    }
+}
+
+/// A view into a vacant entry of a cache table.
+pub struct VacantEntry<'a, K: 'static + TypedMap + Send + Sync + Clone>
+where
+    K::Value: Send + Sync,
+{
+    table: &'a CacheTable,
+    items: RwLockWriteGuard<'a, Items>,
+    key: K,
+}
+
+impl<'a, K: 'static + TypedMap + Send + Sync + Clone> VacantEntry<'a, K>
+where
+    K::Value: Send + Sync,
+{
+    pub(crate) fn new(table: &'a CacheTable, items: RwLockWriteGuard<'a, Items>, key: K) -> Self {
+        Self { table, items, key }
+    }
+
+    /// Returns a reference to this entry's key.
+    pub fn key(&self) -> &K {
+        &self.key
+    }
+
+    /// Takes ownership of the key.
+    pub fn into_key(self) -> K {
+        self.key
+    }
+
+    /// Adds the value to the cache with this entry's key and returns the new item.
+    pub fn insert(mut self, life_span: Duration, value: K::Value) -> CacheItem {
+        let item = CacheItem::new(self.key.clone(), life_span, value);
+        tracing::trace!(
+            "Adding item with lifespan of {:?} to table {}",
+            item.life_span(),
+            self.table.name()
+        );
+        // A vacant entry may still hold a stale item, which is reported as replaced.
+        let mut notifications = Notifications::default();
+        self.table.insert_item(
+            &mut self.items,
+            TypedKey::from_key(self.key),
+            item.clone(),
+            &mut notifications,
+        );
+        drop(self.items);
+        self.table.dispatch(notifications);
+        item
+    }
+}
@@ -1,4 +1,6 @@
-#[derive(thiserror::Error, Debug)]
+use std::sync::Arc;
+
+#[derive(thiserror::Error, Debug, Clone)]
 pub enum Error {
     /// Gets returned when a specific key couldn't be found.
     #[error("Key not found in cache")]
@@ -6,4 +8,12 @@ pub enum Error {
     /// Gets returned when a specific key couldn't be found and loading via the data-loader callback also failed.
     #[error("Key not found and could not be loaded into cache")]
     KeyNotFoundOrLoadable,
+    /// Gets returned when a specific key couldn't be found and the data-loader callback failed.
+    ///
+    /// The source error is shared by all callers waiting on the same load.
+    #[error("Key not found and the data-loader failed")]
+    LoadFailed(#[source] Arc<dyn std::error::Error + Send + Sync>),
+    /// Gets returned when a cached value is not of the value type associated with its key.
+    #[error("Cached value does not match the value type of its key")]
+    ValueTypeMismatch,
 }
@@ -0,0 +1,42 @@
+//! Events reporting the changes of a cache table to its subscribers.
+
+use crate::{
+    item::{CacheItem, RemovalCause},
+    typed::typedkey::TypedKey,
+};
+
+/// CacheEvent is a change of a cache table, as received by its subscribers.
+#[derive(Clone)]
+pub enum CacheEvent {
+    /// An item was added with a new key.
+    Added(CacheItem),
+    /// An item replaced another one with the same key.
+    Replaced {
+        /// The replaced item.
+        old: CacheItem,
+        /// The new item.
+        new: CacheItem,
+    },
+    /// An item was removed for the given cause, other than a replacement or a flush.
+    Removed(CacheItem, RemovalCause),
+    /// All items were removed at once.
+    Flushed,
+}
+
+impl CacheEvent {
+    /// Returns the item the event is about, the new one for replacements, or None for flushes.
+    #[must_use]
+    pub fn item(&self) -> Option<&CacheItem> {
+        match self {
+            CacheEvent::Added(item) | CacheEvent::Removed(item, _) => Some(item),
+            CacheEvent::Replaced { new, .. } => Some(new),
+            CacheEvent::Flushed => None,
+        }
+    }
+
+    /// Returns the key of the item the event is about, or None for flushes.
+    #[must_use]
+    pub fn key(&self) -> Option<&TypedKey> {
+        self.item().map(CacheItem::key)
+    }
+}
@@ -0,0 +1,76 @@
+//! Eviction policies deciding which item leaves a full cache table.
+
+use std::time::Instant;
+
+use crate::item::CacheItem;
+
+/// Rank orders the items of a cache table for eviction, the lowest rank being evicted first.
+pub type Rank = (usize, Instant, usize);
+
+/// EvictionPolicy selects the item to evict when a cache table exceeds its capacity.
+pub trait EvictionPolicy: Send + Sync {
+    /// Returns the item to evict among the given candidates, or None to stop evicting.
+    fn select_victim<'a>(
+        &self,
+        candidates: &mut dyn Iterator<Item = &'a CacheItem>,
+    ) -> Option<&'a CacheItem>;
+
+    /// Returns the rank of the given item, or None if items are only compared by select_victim.
+    ///
+    /// The rank of an item must never decrease while it is cached. Ranked items are kept in order,
+    /// so that the table does not have to go through all of them to find a victim.
+    fn rank(&self, _item: &CacheItem) -> Option<Rank> {
+        None
+    }
+}
+
+/// Evicts the least recently used item.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Lru;
+
+impl EvictionPolicy for Lru {
+    fn select_victim<'a>(
+        &self,
+        candidates: &mut dyn Iterator<Item = &'a CacheItem>,
+    ) -> Option<&'a CacheItem> {
+        candidates.min_by_key(|item| (item.accessed_on(), item.access_count()))
+    }
+
+    fn rank(&self, item: &CacheItem) -> Option<Rank> {
+        Some((0, item.accessed_on(), item.access_count()))
+    }
+}
+
+/// Evicts the least frequently used item, the least recently used one among equals.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Lfu;
+
+impl EvictionPolicy for Lfu {
+    fn select_victim<'a>(
+        &self,
+        candidates: &mut dyn Iterator<Item = &'a CacheItem>,
+    ) -> Option<&'a CacheItem> {
+        candidates.min_by_key(|item| (item.access_count(), item.accessed_on()))
+    }
+
+    fn rank(&self, item: &CacheItem) -> Option<Rank> {
+        Some((item.access_count(), item.accessed_on(), 0))
+    }
+}
+
+/// Evicts the oldest item.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Fifo;
+
+impl EvictionPolicy for Fifo {
+    fn select_victim<'a>(
+        &self,
+        candidates: &mut dyn Iterator<Item = &'a CacheItem>,
+    ) -> Option<&'a CacheItem> {
+        candidates.min_by_key(|item| item.created_on())
+    }
+
+    fn rank(&self, item: &CacheItem) -> Option<Rank> {
+        Some((0, item.created_on(), 0))
+    }
+}
@@ -0,0 +1,133 @@
+//! Custom expiration of items, computed from their keys and values.
+
+use std::{
+    marker::PhantomData,
+    time::{Duration, Instant},
+};
+
+use crate::typed::{typedkey::TypedKey, typedvalue::TypedValue, TypedMap};
+
+/// Expiry computes how long items of key type K live in the cache.
+///
+/// Each hook returns the duration after which the item expires, counted from the given point in
+/// time, or None if it should not expire through this Expiry. The resulting deadline applies on top
+/// of the item's life span and time to live, whichever comes first.
+pub trait Expiry<K: TypedMap>: Send + Sync {
+    /// Called when an item is added to the cache.
+    fn expire_after_create(
+        &self,
+        key: &K,
+        value: &K::Value,
+        created_on: Instant,
+    ) -> Option<Duration>;
+
+    /// Called when an item is read from the cache via the value method.
+    ///
+    /// Parameter remaining is what is left of the current duration. The default keeps it.
+    fn expire_after_read(
+        &self,
+        _key: &K,
+        _value: &K::Value,
+        _read_on: Instant,
+        remaining: Option<Duration>,
+    ) -> Option<Duration> {
+        remaining
+    }
+
+    /// Called when the value of an item is updated or replaced.
+    ///
+    /// Parameter remaining is what is left of the current duration. The default keeps it.
+    fn expire_after_update(
+        &self,
+        _key: &K,
+        _value: &K::Value,
+        _updated_on: Instant,
+        remaining: Option<Duration>,
+    ) -> Option<Duration> {
+        remaining
+    }
+}
+
+/// Type-erased Expiry, as stored by cache tables.
+pub(crate) trait ErasedExpiry: Send + Sync {
+    fn expire_after_create(
+        &self,
+        key: &TypedKey,
+        value: &TypedValue,
+        now: Instant,
+    ) -> Option<Duration>;
+
+    fn expire_after_read(
+        &self,
+        key: &TypedKey,
+        value: &TypedValue,
+        now: Instant,
+        remaining: Option<Duration>,
+    ) -> Option<Duration>;
+
+    fn expire_after_update(
+        &self,
+        key: &TypedKey,
+        value: &TypedValue,
+        now: Instant,
+        remaining: Option<Duration>,
+    ) -> Option<Duration>;
+}
+
+pub(crate) struct TypedExpiry<K, E> {
+    expiry: E,
+    _phantom: PhantomData<fn(&K)>,
+}
+
+impl<K, E> TypedExpiry<K, E> {
+    pub(crate) fn new(expiry: E) -> Self {
+        Self {
+            expiry,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<K: 'static + TypedMap, E: Expiry<K>> ErasedExpiry for TypedExpiry<K, E>
+where
+    K::Value: Send + Sync,
+{
+    fn expire_after_create(
+        &self,
+        key: &TypedKey,
+        value: &TypedValue,
+        now: Instant,
+    ) -> Option<Duration> {
+        let key = key.downcast_ref::<K>()?;
+        let value = value.typed_ref::<K::Value>()?;
+        self.expiry.expire_after_create(key, &value, now)
+    }
+
+    fn expire_after_read(
+        &self,
+        key: &TypedKey,
+        value: &TypedValue,
+        now: Instant,
+        remaining: Option<Duration>,
+    ) -> Option<Duration> {
+        match (key.downcast_ref::<K>(), value.typed_ref::<K::Value>()) {
+            (Some(key), Some(value)) => self.expiry.expire_after_read(key, &value, now, remaining),
+            _ => remaining,
+        }
+    }
+
+    fn expire_after_update(
+        &self,
+        key: &TypedKey,
+        value: &TypedValue,
+        now: Instant,
+        remaining: Option<Duration>,
+    ) -> Option<Duration> {
+        match (key.downcast_ref::<K>(), value.typed_ref::<K::Value>()) {
+            (Some(key), Some(value)) => {
+                self.expiry.expire_after_update(key, &value, now, remaining)
+            }
+            _ => remaining,
+        }
+    }
+}
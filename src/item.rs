@@ -1,6 +1,6 @@
 use std::{
     sync::{
-        atomic::{AtomicUsize, Ordering},
+        atomic::{AtomicBool, AtomicUsize, Ordering},
         Arc,
     },
     time::{Duration, Instant},
@@ -11,6 +11,24 @@ use std::sync::RwLock;
 
 use crate::typed::{typedkey::TypedKey, typedvalue::TypedValue, TypedMap};
 
+/// RemovalCause tells why an item was removed from its cache table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RemovalCause {
+    /// The item was deleted explicitly.
+    Explicit,
+    /// The item expired.
+    Expired,
+    /// The item was replaced by another item with the same key.
+    Replaced,
+    /// The item was evicted to keep the table within its bounds.
+    Evicted,
+    /// The table was flushed.
+    Flushed,
+}
+
+/// Callback triggered right before an item is removed from the cache, along with the cause of the removal.
+pub type AboutToExpireCallback = Box<dyn Fn(&TypedKey, RemovalCause) + Send + Sync>;
+
 // CacheItem is an individual cache item.
 #[derive(Clone)]
 pub struct CacheItem {
@@ -24,15 +42,25 @@ pub(crate) struct CacheItemInner {
     value: TypedValue,
     /// How long will the item live in the cache when not being accessed/kept alive.
     life_span: Duration,
+    /// How long will the item live in the cache after its creation, regardless of accesses.
+    time_to_live: Duration,
     /// Creation timestamp.
-    created_on: Instant,
+    created_on: ArcSwap<Instant>,
     /// Last access timestamp.
     accessed_on: ArcSwap<Instant>,
     /// How often the item was accessed.
     access_count: AtomicUsize,
+    /// Deadline computed by the Expiry of the item's key type.
+    custom_deadline: ArcSwap<Option<Instant>>,
+    /// When the item is removed once it has expired and is kept as stale, None while it is fresh.
+    stale_until: ArcSwap<Option<Instant>>,
+    /// Whether a background reload of the item is in flight.
+    refreshing: AtomicBool,
+    /// The weight of the item, as computed by the weigher of its table.
+    weight: AtomicUsize,
     #[allow(clippy::type_complexity)]
     /// Callback method triggered right before removing the item from the cache.
-    pub(crate) about_to_expire: RwLock<Vec<Box<dyn Fn(&TypedKey) + Send + Sync>>>,
+    pub(crate) about_to_expire: RwLock<Vec<Arc<dyn Fn(&TypedKey, RemovalCause) + Send + Sync>>>,
 }
 
 impl CacheItem {
@@ -46,6 +74,24 @@ impl CacheItem {
         life_span: Duration,
         value: K::Value,
     ) -> Self
+    where
+        K::Value: Send + Sync,
+    {
+        Self::with_time_to_live(key, life_span, Duration::ZERO, value)
+    }
+
+    /// Returns a newly created CacheItem which also expires a fixed time after its creation.
+    ///
+    /// Parameter key is the item's cache-key.
+    /// Parameter life_span determines after which time period without an access the item will get removed from the cache.
+    /// Parameter time_to_live determines after which time period since its creation the item will get removed from the cache, even if it is being accessed.
+    /// Parameter value is the item's value.
+    pub fn with_time_to_live<K: 'static + TypedMap + Send + Sync>(
+        key: K,
+        life_span: Duration,
+        time_to_live: Duration,
+        value: K::Value,
+    ) -> Self
     where
         K::Value: Send + Sync,
     {
@@ -55,26 +101,91 @@ impl CacheItem {
                 key: TypedKey::from_key(key),
                 value: TypedValue::from_value(value),
                 life_span,
-                created_on: t,
+                time_to_live,
+                created_on: ArcSwap::from_pointee(t),
                 accessed_on: ArcSwap::from_pointee(t),
                 access_count: AtomicUsize::new(0),
+                custom_deadline: ArcSwap::from_pointee(None),
+                stale_until: ArcSwap::from_pointee(None),
+                refreshing: AtomicBool::new(false),
+                weight: AtomicUsize::new(1),
                 about_to_expire: RwLock::new(Vec::new()),
             }),
         }
     }
 
+    /// Returns whether both handles refer to the same item.
+    pub(crate) fn ptr_eq(&self, other: &CacheItem) -> bool {
+        Arc::ptr_eq(&self.inner, &other.inner)
+    }
+
     /// Marks an item to be kept for another expire_duration period.
+    ///
+    /// This uses the system time. Tables keep their items alive according to their own clock.
     pub fn keep_alive(&self) {
-        self.inner.accessed_on.store(Arc::new(Instant::now()));
+        self.keep_alive_at(Instant::now());
+    }
+
+    pub(crate) fn keep_alive_at(&self, now: Instant) {
+        self.inner.accessed_on.store(Arc::new(now));
         self.inner.access_count.fetch_add(1, Ordering::Relaxed);
     }
 
+    /// Stamps the item as created and last accessed at the given time.
+    pub(crate) fn stamp(&self, now: Instant) {
+        self.inner.created_on.store(Arc::new(now));
+        self.inner.accessed_on.store(Arc::new(now));
+    }
+
     #[must_use]
     /// Returns this item's expiration duration.
     pub fn life_span(&self) -> Duration {
         self.inner.life_span
     }
 
+    #[must_use]
+    /// Returns this item's time to live since its creation.
+    pub fn time_to_live(&self) -> Duration {
+        self.inner.time_to_live
+    }
+
+    #[must_use]
+    /// Returns when this item expires, whichever of its idle, absolute and custom deadlines comes first.
+    ///
+    /// Once the item is stale, this is when it is removed from the cache.
+    /// Returns None if the item never expires.
+    pub fn expires_at(&self) -> Option<Instant> {
+        if let Some(stale_until) = **self.inner.stale_until.load() {
+            return Some(stale_until);
+        }
+        let idle = (self.inner.life_span > Duration::ZERO)
+            .then(|| self.accessed_on() + self.inner.life_span);
+        let absolute = (self.inner.time_to_live > Duration::ZERO)
+            .then(|| self.created_on() + self.inner.time_to_live);
+        [idle, absolute, self.custom_deadline()]
+            .into_iter()
+            .flatten()
+            .min()
+    }
+
+    pub(crate) fn custom_deadline(&self) -> Option<Instant> {
+        **self.inner.custom_deadline.load()
+    }
+
+    pub(crate) fn set_custom_deadline(&self, deadline: Option<Instant>) {
+        self.inner.custom_deadline.store(Arc::new(deadline));
+    }
+
+    #[must_use]
+    /// Returns whether this item has expired and is only kept for the grace period of its table.
+    pub fn is_stale(&self) -> bool {
+        self.inner.stale_until.load().is_some()
+    }
+
+    pub(crate) fn set_stale_until(&self, stale_until: Instant) {
+        self.inner.stale_until.store(Arc::new(Some(stale_until)));
+    }
+
     #[must_use]
     /// Returns when this item was last accessed.
     pub fn accessed_on(&self) -> Instant {
@@ -84,7 +195,7 @@ impl CacheItem {
     #[must_use]
     /// Returns when this item was added to the cache.
     pub fn created_on(&self) -> Instant {
-        self.inner.created_on
+        **self.inner.created_on.load()
     }
 
     #[must_use]
@@ -93,6 +204,16 @@ impl CacheItem {
         self.inner.access_count.load(Ordering::Relaxed)
     }
 
+    #[must_use]
+    /// Returns the weight of this item within its table.
+    pub fn weight(&self) -> usize {
+        self.inner.weight.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn set_weight(&self, weight: usize) {
+        self.inner.weight.store(weight, Ordering::Relaxed);
+    }
+
     #[must_use]
     /// Returns the key of this cached item.
     pub fn key(&self) -> &TypedKey {
@@ -105,18 +226,48 @@ impl CacheItem {
         &self.inner.value
     }
 
+    /// Marks the item as being refreshed, returns false if it already is.
+    pub(crate) fn start_refresh(&self) -> bool {
+        !self.inner.refreshing.swap(true, Ordering::AcqRel)
+    }
+
+    pub(crate) fn end_refresh(&self) {
+        self.inner.refreshing.store(false, Ordering::Release);
+    }
+
+    /// Moves the about_to_expire callbacks of the given item to this one.
+    pub(crate) fn inherit_about_to_expire_callbacks(&self, other: &CacheItem) {
+        let callbacks = std::mem::take(&mut *other.inner.about_to_expire.write().unwrap());
+        self.inner
+            .about_to_expire
+            .write()
+            .unwrap()
+            .extend(callbacks);
+    }
+
+    /// Carries over the access count of the given item, which this one replaces.
+    pub(crate) fn inherit_access_count(&self, other: &CacheItem) {
+        self.inner
+            .access_count
+            .store(other.access_count(), Ordering::Relaxed);
+    }
+
     /// Configures a callback, which will be called right before the item is about to be removed from the cache.
-    pub fn set_about_to_expire_callback(&self, f: Box<dyn Fn(&TypedKey) + Send + Sync>) {
+    pub fn set_about_to_expire_callback(&self, f: AboutToExpireCallback) {
         let mut guard = self.inner.about_to_expire.write().unwrap();
-        if guard.len() > 0 {
+        if !guard.is_empty() {
             guard.clear();
         }
-        guard.push(f);
+        guard.push(Arc::from(f));
     }
 
     /// Appends a new callback to the about_to_expire queue.
-    pub fn add_about_to_expire_callback(&self, f: Box<dyn Fn(&TypedKey) + Send + Sync>) {
-        self.inner.about_to_expire.write().unwrap().push(f);
+    pub fn add_about_to_expire_callback(&self, f: AboutToExpireCallback) {
+        self.inner
+            .about_to_expire
+            .write()
+            .unwrap()
+            .push(Arc::from(f));
     }
 
     /// Empties the about to expire callback queue.
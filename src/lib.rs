@@ -41,8 +41,19 @@
 //! ```
 //!
 
+pub mod clock;
+pub mod entry;
 pub mod error;
+pub mod event;
+pub mod eviction;
+pub mod expiry;
 pub mod item;
+pub mod loader;
+pub mod metrics;
+mod notifier;
+pub mod quota;
+pub mod stale;
+pub mod stats;
 pub mod table;
 pub mod typed;
 
@@ -50,7 +61,7 @@ use std::collections::HashMap;
 
 use std::sync::RwLock;
 
-use crate::table::CacheTable;
+use crate::{eviction::EvictionPolicy, table::CacheTable};
 
 lazy_static::lazy_static! {
     pub static ref CACHE: RwLock<HashMap<String, CacheTable>> = RwLock::new(HashMap::new());
@@ -73,3 +84,21 @@ pub fn cache(name: String) -> CacheTable {
         table
     }
 }
+
+/// Cache with capacity returns the existing cache table with given name or creates a new one
+/// holding at most capacity items if the table does not exist yet.
+///
+/// The capacity and eviction policy only apply to a newly created table.
+pub fn cache_with_capacity(
+    name: String,
+    capacity: usize,
+    eviction_policy: impl EvictionPolicy + 'static,
+) -> CacheTable {
+    let mut cache = CACHE.write().unwrap();
+    cache
+        .entry(name)
+        .or_insert_with_key(|name| {
+            CacheTable::with_capacity(name.clone(), capacity, eviction_policy)
+        })
+        .to_owned()
+}
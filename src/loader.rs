@@ -0,0 +1,58 @@
+//! Types of the data loaders filling cache tables on misses.
+
+use std::{
+    collections::HashMap,
+    error::Error,
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use tokio::sync::watch;
+
+use crate::{
+    item::CacheItem,
+    typed::{typedkey::TypedKey, TypedMap},
+};
+
+/// An owned, type-erased future, as returned by asynchronous data loaders.
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+
+/// A type-erased error, as returned by fallible data loaders.
+pub type BoxError = Box<dyn Error + Send + Sync>;
+
+/// A data loader, as stored by cache tables.
+pub(crate) type Loader = Arc<dyn Fn(TypedKey) -> Result<Option<CacheItem>, BoxError> + Send + Sync>;
+
+/// An asynchronous data loader, as stored by cache tables.
+pub(crate) type AsyncLoader =
+    Arc<dyn Fn(TypedKey) -> BoxFuture<'static, Result<Option<CacheItem>, BoxError>> + Send + Sync>;
+
+/// A batch loader for keys of type K, as stored by cache tables.
+pub(crate) struct BatchLoader<K: TypedMap> {
+    #[allow(clippy::type_complexity)]
+    pub(crate) load: Box<dyn Fn(Vec<K>) -> HashMap<K, K::Value> + Send + Sync>,
+    /// The life span of loaded items.
+    pub(crate) life_span: Duration,
+    /// How long misses are collected before they are loaded together.
+    pub(crate) window: Duration,
+    /// The batch currently collecting misses.
+    pub(crate) pending: Mutex<Option<Arc<Batch<K>>>>,
+}
+
+/// Batch collects misses from concurrent tasks to load them in a single call.
+pub(crate) struct Batch<K> {
+    pub(crate) keys: Mutex<Vec<K>>,
+    /// Set once the keys have been loaded.
+    pub(crate) done: watch::Sender<bool>,
+}
+
+impl<K> Batch<K> {
+    pub(crate) fn new() -> Self {
+        Self {
+            keys: Mutex::new(Vec::new()),
+            done: watch::Sender::new(false),
+        }
+    }
+}
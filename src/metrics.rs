@@ -0,0 +1,184 @@
+//! Prometheus text exposition of cache table statistics.
+
+use std::fmt::Write;
+
+use crate::{
+    stats::{Stats, TypeStats},
+    table::CacheTable,
+    CACHE,
+};
+
+/// The name, type, help text and value of a metric of each table.
+type TableMetric = (
+    &'static str,
+    &'static str,
+    &'static str,
+    fn(&CacheTable, &Stats) -> String,
+);
+
+/// The name, type, help text and value of a metric of each key type within a table.
+type TypeMetric = (
+    &'static str,
+    &'static str,
+    &'static str,
+    fn(&TypeStats) -> String,
+);
+
+const TABLE_METRICS: [TableMetric; 11] = [
+    (
+        "items",
+        "gauge",
+        "Number of items stored in the table.",
+        |t, _| t.count().to_string(),
+    ),
+    (
+        "weight",
+        "gauge",
+        "Total weight of the items stored in the table.",
+        |t, _| t.weight().to_string(),
+    ),
+    (
+        "hits_total",
+        "counter",
+        "Number of lookups which found an item.",
+        |_, s| s.hits.to_string(),
+    ),
+    (
+        "misses_total",
+        "counter",
+        "Number of lookups which did not find an item.",
+        |_, s| s.misses.to_string(),
+    ),
+    (
+        "loads_total",
+        "counter",
+        "Number of calls to a data-loader.",
+        |_, s| s.loads.to_string(),
+    ),
+    (
+        "load_failures_total",
+        "counter",
+        "Number of calls to a data-loader which returned an error.",
+        |_, s| s.load_failures.to_string(),
+    ),
+    (
+        "load_seconds_total",
+        "counter",
+        "Total time spent in data-loaders.",
+        |_, s| s.total_load_time.as_secs_f64().to_string(),
+    ),
+    (
+        "insertions_total",
+        "counter",
+        "Number of items added to the table.",
+        |_, s| s.insertions.to_string(),
+    ),
+    (
+        "deletions_total",
+        "counter",
+        "Number of items explicitly deleted from the table.",
+        |_, s| s.deletions.to_string(),
+    ),
+    (
+        "expirations_total",
+        "counter",
+        "Number of items removed from the table after they expired.",
+        |_, s| s.expirations.to_string(),
+    ),
+    (
+        "evictions_total",
+        "counter",
+        "Number of items evicted from the table.",
+        |_, s| s.evictions.to_string(),
+    ),
+];
+const TYPE_METRICS: [TypeMetric; 4] = [
+    (
+        "key_type_items",
+        "gauge",
+        "Number of items of a key type stored in the table.",
+        |s| s.count.to_string(),
+    ),
+    (
+        "key_type_hits_total",
+        "counter",
+        "Number of lookups of a key type which found an item.",
+        |s| s.hits.to_string(),
+    ),
+    (
+        "key_type_misses_total",
+        "counter",
+        "Number of lookups of a key type which did not find an item.",
+        |s| s.misses.to_string(),
+    ),
+    (
+        "key_type_expirations_total",
+        "counter",
+        "Number of items of a key type removed from the table after they expired.",
+        |s| s.expirations.to_string(),
+    ),
+];
+
+/// Returns the sizes and statistics of all registered cache tables in the Prometheus text format.
+///
+/// The output can be served as is, with content type `text/plain; version=0.0.4`.
+pub fn render_all() -> String {
+    let tables: Vec<CacheTable> = CACHE.read().unwrap().values().cloned().collect();
+    render(&tables)
+}
+
+/// Returns the sizes and statistics of the given cache tables in the Prometheus text format.
+///
+/// Tables are ordered by name and key types by type name, so the output is stable.
+pub fn render(tables: &[CacheTable]) -> String {
+    let mut tables: Vec<_> = tables
+        .iter()
+        .map(|table| {
+            let mut by_type: Vec<_> = table.stats_by_type().into_values().collect();
+            by_type.sort_by_key(|stats| stats.type_name);
+            (table, table.stats(), by_type)
+        })
+        .collect();
+    tables.sort_by(|a, b| a.0.name().cmp(b.0.name()));
+
+    let mut out = String::new();
+    for (name, kind, help, value) in TABLE_METRICS {
+        header(&mut out, name, kind, help);
+        for (table, stats, _) in &tables {
+            _ = writeln!(
+                out,
+                "typedcache_{name}{{table=\"{}\"}} {}",
+                escape(table.name()),
+                value(table, stats)
+            );
+        }
+    }
+    for (name, kind, help, value) in TYPE_METRICS {
+        header(&mut out, name, kind, help);
+        for (table, _, by_type) in &tables {
+            for stats in by_type {
+                _ = writeln!(
+                    out,
+                    "typedcache_{name}{{table=\"{}\",key_type=\"{}\"}} {}",
+                    escape(table.name()),
+                    escape(stats.type_name),
+                    value(stats)
+                );
+            }
+        }
+    }
+    out
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    _ = writeln!(out, "# HELP typedcache_{name} {help}");
+    _ = writeln!(out, "# TYPE typedcache_{name} {kind}");
+}
+
+/// Escapes a label value.
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
@@ -0,0 +1,85 @@
+//! Notifier driving the asynchronous callbacks of a cache table.
+
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+use tokio::{
+    sync::{mpsc, watch},
+    task::JoinSet,
+};
+
+use crate::loader::BoxFuture;
+
+/// Notifier runs the futures of asynchronous callbacks on a dedicated task.
+///
+/// Futures are started in the order they are queued, with at most `concurrency` of them running at once.
+pub(crate) struct Notifier {
+    tx: mpsc::UnboundedSender<BoxFuture<'static, ()>>,
+    concurrency: Arc<AtomicUsize>,
+    /// The number of queued or running futures.
+    pending: Arc<watch::Sender<usize>>,
+}
+
+impl Notifier {
+    /// Spawns the notification task of the table with the given name.
+    pub(crate) fn spawn(name: String) -> Self {
+        let (tx, mut rx) = mpsc::unbounded_channel::<BoxFuture<'static, ()>>();
+        let concurrency = Arc::new(AtomicUsize::new(1));
+        let pending = Arc::new(watch::Sender::new(0));
+        tokio::spawn({
+            let concurrency = concurrency.clone();
+            let pending = pending.clone();
+            async move {
+                let mut running = JoinSet::new();
+                while let Some(future) = rx.recv().await {
+                    while running.try_join_next().is_some() {}
+                    while running.len() >= concurrency.load(Ordering::Relaxed).max(1) {
+                        running.join_next().await;
+                    }
+                    let done = Done(pending.clone());
+                    running.spawn(async move {
+                        future.await;
+                        drop(done);
+                    });
+                }
+                tracing::trace!("Notifier of cache table {} is closed", name);
+            }
+        });
+        Self {
+            tx,
+            concurrency,
+            pending,
+        }
+    }
+
+    /// Queues the future of an asynchronous callback.
+    pub(crate) fn notify(&self, future: BoxFuture<'static, ()>) {
+        self.pending.send_modify(|pending| *pending += 1);
+        if let Err(e) = self.tx.send(future) {
+            self.pending.send_modify(|pending| *pending -= 1);
+            tracing::error!("Error sending to channel for notifications: {}", e);
+        }
+    }
+
+    pub(crate) fn set_concurrency(&self, concurrency: usize) {
+        self.concurrency.store(concurrency, Ordering::Relaxed);
+    }
+
+    /// Waits until all queued futures have completed.
+    pub(crate) async fn wait(&self) {
+        let mut pending = self.pending.subscribe();
+        // The sender lives as long as self.
+        _ = pending.wait_for(|pending| *pending == 0).await;
+    }
+}
+
+/// Done marks a queued future as completed once dropped, even if it panicked.
+struct Done(Arc<watch::Sender<usize>>);
+
+impl Drop for Done {
+    fn drop(&mut self) {
+        self.0.send_modify(|pending| *pending -= 1);
+    }
+}
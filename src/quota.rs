@@ -0,0 +1,28 @@
+//! Per key type quotas within a cache table.
+
+/// Quota bounds the items of a single key type within a cache table.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Quota {
+    /// The maximum number of items, zero means unbounded.
+    pub max_count: usize,
+    /// The maximum total weight of the items, zero means unbounded.
+    pub max_weight: usize,
+}
+
+impl Quota {
+    /// Returns whether the given usage exceeds this quota.
+    #[must_use]
+    pub fn is_exceeded_by(&self, usage: &Usage) -> bool {
+        (self.max_count > 0 && usage.count > self.max_count)
+            || (self.max_weight > 0 && usage.weight > self.max_weight)
+    }
+}
+
+/// Usage is the number and total weight of the items of a single key type within a cache table.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Usage {
+    /// The number of items.
+    pub count: usize,
+    /// The total weight of the items.
+    pub weight: usize,
+}
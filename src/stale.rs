@@ -0,0 +1,13 @@
+//! Serving modes for items which have expired but are still within the grace period of their table.
+
+/// StaleMode determines whether a lookup may return an item which has expired within the grace period.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum StaleMode {
+    /// Stale items are treated as missing and reloaded.
+    #[default]
+    Never,
+    /// Stale items are returned right away while being reloaded in the background.
+    WhileRevalidate,
+    /// Stale items are reloaded and returned only if the data-loader fails.
+    IfError,
+}
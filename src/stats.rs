@@ -0,0 +1,231 @@
+//! Statistics of cache tables.
+
+use std::{
+    any::TypeId,
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, RwLock,
+    },
+    time::Duration,
+};
+
+use crate::typed::typedkey::TypedKey;
+
+/// Stats is a snapshot of the statistics of a cache table.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Stats {
+    /// The number of lookups which found an item.
+    pub hits: u64,
+    /// The number of lookups which did not find an item.
+    pub misses: u64,
+    /// The number of calls to a data-loader.
+    pub loads: u64,
+    /// The number of calls to a data-loader which returned an error.
+    pub load_failures: u64,
+    /// The total time spent in data-loaders.
+    pub total_load_time: Duration,
+    /// The number of items added to the table, including replacements.
+    pub insertions: u64,
+    /// The number of items explicitly deleted from the table.
+    pub deletions: u64,
+    /// The number of items removed from the table after they expired.
+    pub expirations: u64,
+    /// The number of items evicted from the table to keep it within its bounds.
+    pub evictions: u64,
+}
+
+impl Stats {
+    /// Returns the ratio of lookups which found an item, 1 if there were no lookups.
+    #[must_use]
+    pub fn hit_rate(&self) -> f64 {
+        match self.hits + self.misses {
+            0 => 1.0,
+            lookups => self.hits as f64 / lookups as f64,
+        }
+    }
+}
+
+/// TypeStats is a snapshot of the statistics of a single key type within a cache table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TypeStats {
+    /// The name of the key type, for display purposes only.
+    pub type_name: &'static str,
+    /// The number of items currently stored in the table, not counting stale ones.
+    pub count: usize,
+    /// The number of lookups which found an item.
+    pub hits: u64,
+    /// The number of lookups which did not find an item.
+    pub misses: u64,
+    /// The number of items removed from the table after they expired.
+    pub expirations: u64,
+}
+
+/// KeyType identifies the key type statistics are recorded for.
+#[derive(Clone, Copy)]
+pub(crate) struct KeyType {
+    id: TypeId,
+    name: &'static str,
+}
+
+impl KeyType {
+    pub(crate) fn of<K: 'static>() -> Self {
+        Self {
+            id: TypeId::of::<K>(),
+            name: std::any::type_name::<K>(),
+        }
+    }
+
+    pub(crate) fn of_key(key: &TypedKey) -> Self {
+        Self {
+            id: key.key_type_id(),
+            name: key.key_type_name(),
+        }
+    }
+}
+
+/// TypeCounters maintains the statistics of a single key type.
+struct TypeCounters {
+    type_name: &'static str,
+    hits: AtomicU64,
+    misses: AtomicU64,
+    expirations: AtomicU64,
+}
+
+/// Counters maintains the statistics of a cache table.
+#[derive(Default)]
+pub(crate) struct Counters {
+    hits: AtomicU64,
+    misses: AtomicU64,
+    loads: AtomicU64,
+    load_failures: AtomicU64,
+    total_load_nanos: AtomicU64,
+    insertions: AtomicU64,
+    deletions: AtomicU64,
+    expirations: AtomicU64,
+    evictions: AtomicU64,
+    by_type: RwLock<HashMap<TypeId, Arc<TypeCounters>>>,
+}
+
+impl Counters {
+    pub(crate) fn record_hit(&self, key_type: KeyType) {
+        self.hits.fetch_add(1, Ordering::Relaxed);
+        self.by_type(key_type).hits.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_misses(&self, key_type: KeyType, n: usize) {
+        self.misses.fetch_add(n as u64, Ordering::Relaxed);
+        self.by_type(key_type)
+            .misses
+            .fetch_add(n as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_load(&self, elapsed: Duration, failed: bool) {
+        self.loads.fetch_add(1, Ordering::Relaxed);
+        if failed {
+            self.load_failures.fetch_add(1, Ordering::Relaxed);
+        }
+        self.total_load_nanos.fetch_add(
+            elapsed.as_nanos().try_into().unwrap_or(u64::MAX),
+            Ordering::Relaxed,
+        );
+    }
+
+    pub(crate) fn record_insertion(&self, key_type: KeyType) {
+        self.insertions.fetch_add(1, Ordering::Relaxed);
+        self.by_type(key_type);
+    }
+
+    pub(crate) fn record_deletion(&self) {
+        self.deletions.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_expiration(&self, key_type: KeyType) {
+        self.expirations.fetch_add(1, Ordering::Relaxed);
+        self.by_type(key_type)
+            .expirations
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_eviction(&self) {
+        self.evictions.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn snapshot(&self) -> Stats {
+        Stats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            loads: self.loads.load(Ordering::Relaxed),
+            load_failures: self.load_failures.load(Ordering::Relaxed),
+            total_load_time: Duration::from_nanos(self.total_load_nanos.load(Ordering::Relaxed)),
+            insertions: self.insertions.load(Ordering::Relaxed),
+            deletions: self.deletions.load(Ordering::Relaxed),
+            expirations: self.expirations.load(Ordering::Relaxed),
+            evictions: self.evictions.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Returns the statistics by key type, along with the current usage of each type.
+    pub(crate) fn snapshot_by_type(
+        &self,
+        counts: &HashMap<TypeId, usize>,
+    ) -> HashMap<TypeId, TypeStats> {
+        self.by_type
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(type_id, counters)| {
+                let stats = TypeStats {
+                    type_name: counters.type_name,
+                    count: counts.get(type_id).copied().unwrap_or_default(),
+                    hits: counters.hits.load(Ordering::Relaxed),
+                    misses: counters.misses.load(Ordering::Relaxed),
+                    expirations: counters.expirations.load(Ordering::Relaxed),
+                };
+                (*type_id, stats)
+            })
+            .collect()
+    }
+
+    /// Returns the counters of the given key type, registering it if needed.
+    fn by_type(&self, key_type: KeyType) -> Arc<TypeCounters> {
+        if let Some(counters) = self.by_type.read().unwrap().get(&key_type.id) {
+            return counters.clone();
+        }
+        self.by_type
+            .write()
+            .unwrap()
+            .entry(key_type.id)
+            .or_insert_with(|| {
+                Arc::new(TypeCounters {
+                    type_name: key_type.name,
+                    hits: AtomicU64::new(0),
+                    misses: AtomicU64::new(0),
+                    expirations: AtomicU64::new(0),
+                })
+            })
+            .clone()
+    }
+
+    /// Resets all counters. Key types stay registered, so they keep being reported.
+    pub(crate) fn reset(&self) {
+        for counter in [
+            &self.hits,
+            &self.misses,
+            &self.loads,
+            &self.load_failures,
+            &self.total_load_nanos,
+            &self.insertions,
+            &self.deletions,
+            &self.expirations,
+            &self.evictions,
+        ] {
+            counter.store(0, Ordering::Relaxed);
+        }
+        for counters in self.by_type.read().unwrap().values() {
+            counters.hits.store(0, Ordering::Relaxed);
+            counters.misses.store(0, Ordering::Relaxed);
+            counters.expirations.store(0, Ordering::Relaxed);
+        }
+    }
+}
@@ -1,22 +1,47 @@
 use std::{
-    collections::HashMap,
-    sync::Arc,
+    any::{Any, TypeId},
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap, HashSet},
+    future::Future,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex, Weak,
+    },
     time::{Duration, Instant},
 };
 
 use arc_swap::ArcSwap;
 use std::sync::RwLock;
-use tokio::sync::mpsc::{self, UnboundedSender};
+use tokio::sync::{
+    broadcast,
+    mpsc::{self, UnboundedSender},
+    OnceCell,
+};
+use tokio_stream::wrappers::BroadcastStream;
 
 use crate::{
+    clock::{Clock, SystemClock},
+    entry::{Entry, OccupiedEntry, VacantEntry},
     error::Error,
-    item::CacheItem,
+    event::CacheEvent,
+    eviction::{EvictionPolicy, Lru, Rank},
+    expiry::{ErasedExpiry, Expiry, TypedExpiry},
+    item::{CacheItem, CacheItemInner, RemovalCause},
+    loader::{AsyncLoader, Batch, BatchLoader, BoxError, BoxFuture, Loader},
+    notifier::Notifier,
+    quota::{Quota, Usage},
+    stale::StaleMode,
+    stats::{Counters, KeyType, Stats, TypeStats},
     typed::{
         typedkey::{Key, TypedKey, TypedKeyRef},
+        typedvalue::{TypedRef, TypedValue},
         TypedMap,
     },
 };
 
+/// The number of events buffered for each subscriber of a table.
+const EVENTS_CAPACITY: usize = 1024;
+
 /// CacheTable is a table within the cache
 #[derive(Clone)]
 pub struct CacheTable {
@@ -27,33 +52,113 @@ struct CacheTableInner {
     /// The table's name.
     name: String,
     /// All cached items.
-    items: RwLock<HashMap<TypedKey, CacheItem>>,
-    /// The interval for cleaning up expired items.
-    clean_up_interval: ArcSwap<Duration>,
+    items: RwLock<Items>,
+    /// The maximum number of items, zero means unbounded.
+    capacity: AtomicUsize,
+    /// The maximum total weight of all items, zero means unbounded.
+    max_weight: AtomicUsize,
     #[allow(clippy::type_complexity)]
+    /// Weighers computing the weight of items, by key type.
+    weighers: RwLock<HashMap<TypeId, Box<dyn Fn(&TypedKey, &TypedValue) -> usize + Send + Sync>>>,
+    /// Quotas bounding the items, by key type.
+    quotas: RwLock<HashMap<TypeId, Quota>>,
+    /// Expiries computing custom deadlines of items, by key type.
+    expiries: RwLock<HashMap<TypeId, Arc<dyn ErasedExpiry>>>,
+    /// The policy selecting which items to evict when the capacity is exceeded.
+    eviction_policy: Arc<dyn EvictionPolicy>,
+    /// The time source for timestamps and deadlines of items.
+    clock: RwLock<Arc<dyn Clock>>,
+    /// When the next clean up of expired items is due.
+    next_clean_up: ArcSwap<Option<Instant>>,
     /// Callback method triggered when trying to load a non-existing key.
-    load_data: RwLock<Option<Box<dyn Fn(TypedKey) -> Option<CacheItem> + Send + Sync>>>,
+    load_data: RwLock<Option<Loader>>,
+    /// Asynchronous callback method triggered when trying to load a non-existing key via value_async.
+    load_data_async: RwLock<Option<AsyncLoader>>,
+    /// Data-loaders by key type, taking precedence over the table-wide ones.
+    loaders: RwLock<HashMap<TypeId, Loader>>,
+    /// The age after which items are reloaded in the background when read, zero meaning never.
+    refresh_after: ArcSwap<Duration>,
+    /// How long expired items are kept as stale, zero meaning they are removed right away.
+    grace_period: ArcSwap<Duration>,
+    /// Refresh ages by key type, taking precedence over the table-wide one.
+    refresh_after_by_type: RwLock<HashMap<TypeId, Duration>>,
+    /// Batch loaders by key type, each holding an `Arc<BatchLoader<K>>`.
+    batch_loaders: RwLock<HashMap<TypeId, Arc<dyn Any + Send + Sync>>>,
+    #[allow(clippy::type_complexity)]
+    /// Loads in flight, shared by all concurrent misses of the same key.
+    loading: Mutex<HashMap<TypedKey, Arc<OnceCell<Result<Option<CacheItem>, Error>>>>>,
+    /// The statistics of the table.
+    stats: Counters,
+    /// How long keys the data-loader could not find are remembered, zero meaning they are not.
+    negative_time_to_live: ArcSwap<Duration>,
+    /// Keys the data-loader could not find, along with when they are forgotten.
+    misses: Mutex<HashMap<TypedKey, Instant>>,
     #[allow(clippy::type_complexity)]
     /// Callback methods triggered when an item is added to the cache.
-    added_item: RwLock<Vec<Box<dyn Fn(CacheItem) + Send + Sync>>>,
+    added_item: RwLock<Vec<Arc<dyn Fn(CacheItem) + Send + Sync>>>,
     #[allow(clippy::type_complexity)]
     /// Callback methods triggered when an item is about to be deleted from the cache.
-    about_to_delete_item: RwLock<Vec<Box<dyn Fn(CacheItem) + Send + Sync>>>,
+    about_to_delete_item: RwLock<Vec<Arc<dyn Fn(CacheItem, RemovalCause) + Send + Sync>>>,
+    #[allow(clippy::type_complexity)]
+    /// Asynchronous callback methods triggered when an item is added to the cache.
+    added_item_async: RwLock<Vec<Arc<dyn Fn(CacheItem) -> BoxFuture<'static, ()> + Send + Sync>>>,
+    #[allow(clippy::type_complexity)]
+    /// Asynchronous callback methods triggered when an item is about to be deleted from the cache.
+    about_to_delete_item_async:
+        RwLock<Vec<Arc<dyn Fn(CacheItem, RemovalCause) -> BoxFuture<'static, ()> + Send + Sync>>>,
+    /// Drives the futures of the asynchronous callbacks.
+    notifier: Notifier,
+    /// Sends the changes of the table to its subscribers.
+    events: broadcast::Sender<CacheEvent>,
     tx: UnboundedSender<()>,
 }
 
 impl CacheTable {
     #[must_use]
     pub fn new(name: String) -> Self {
+        Self::with_capacity(name, 0, Lru)
+    }
+
+    /// Returns a new table holding at most capacity items, zero meaning unbounded.
+    ///
+    /// Parameter eviction_policy selects the items to evict once the capacity is exceeded.
+    #[must_use]
+    pub fn with_capacity(
+        name: String,
+        capacity: usize,
+        eviction_policy: impl EvictionPolicy + 'static,
+    ) -> Self {
         let (tx, mut rx) = mpsc::unbounded_channel::<()>();
+        let eviction_policy: Arc<dyn EvictionPolicy> = Arc::new(eviction_policy);
         let cache_table = Self {
             inner: Arc::new(CacheTableInner {
-                name,
-                items: RwLock::new(HashMap::new()),
-                clean_up_interval: ArcSwap::from_pointee(Duration::ZERO),
+                name: name.clone(),
+                items: RwLock::new(Items::new(eviction_policy.clone())),
+                capacity: AtomicUsize::new(capacity),
+                max_weight: AtomicUsize::new(0),
+                weighers: RwLock::new(HashMap::new()),
+                quotas: RwLock::new(HashMap::new()),
+                expiries: RwLock::new(HashMap::new()),
+                eviction_policy,
+                clock: RwLock::new(Arc::new(SystemClock)),
+                next_clean_up: ArcSwap::from_pointee(None),
                 load_data: RwLock::new(None),
+                load_data_async: RwLock::new(None),
+                loaders: RwLock::new(HashMap::new()),
+                refresh_after: ArcSwap::from_pointee(Duration::ZERO),
+                refresh_after_by_type: RwLock::new(HashMap::new()),
+                grace_period: ArcSwap::from_pointee(Duration::ZERO),
+                batch_loaders: RwLock::new(HashMap::new()),
+                loading: Mutex::new(HashMap::new()),
+                stats: Counters::default(),
+                negative_time_to_live: ArcSwap::from_pointee(Duration::ZERO),
+                misses: Mutex::new(HashMap::new()),
                 added_item: RwLock::new(Vec::new()),
                 about_to_delete_item: RwLock::new(Vec::new()),
+                added_item_async: RwLock::new(Vec::new()),
+                about_to_delete_item_async: RwLock::new(Vec::new()),
+                notifier: Notifier::spawn(name.clone()),
+                events: broadcast::channel(EVENTS_CAPACITY).0,
                 tx,
             }),
         };
@@ -65,57 +170,10 @@ impl CacheTable {
                     tokio::select! {
                         _ = tokio::time::sleep(clean_up_timer) => {
                             tracing::trace!("Expiration check triggered after {:?} for table {}", clean_up_timer, cache_table.inner.name);
-                            let mut smallest_duration = Duration::from_secs(0);
-                            {
-                                let now = Instant::now();
-                                let mut to_remove = Vec::new();
-                                let mut w = cache_table.inner.items.write().unwrap();
-
-                                for (_, item) in w.iter() {
-                                    let life_span = item.life_span();
-                                    let accessed_on = item.accessed_on();
-                                    if life_span == Duration::ZERO {
-                                        continue;
-                                    }
-                                    if now.duration_since(accessed_on) >= life_span {
-                                        to_remove.push(item.clone());
-                                    } else {
-                                        let duration = life_span - now.duration_since(accessed_on);
-                                        if smallest_duration == Duration::from_secs(0) || duration < smallest_duration {
-                                            smallest_duration = duration;
-                                        }
-                                    }
-                                }
-
-                                for item in to_remove {
-                                    if let Some(item) = w.remove(item.key()) {
-                                        {
-                                            let about_to_delete_item = cache_table.inner.about_to_delete_item.read().unwrap();
-                                            if !about_to_delete_item.is_empty() {
-                                                for callback in about_to_delete_item.iter() {
-                                                    callback(item.clone());
-                                                }
-                                            }
-                                        }
-
-                                        {
-                                            let about_to_expire = item.inner.about_to_expire.read().unwrap();
-                                            if !about_to_expire.is_empty() {
-                                                for callback in about_to_expire.iter() {
-                                                    callback(item.key());
-                                                }
-                                            }
-                                        }
-                                    }
-                                }
-                            }
-                            if smallest_duration <= Duration::ZERO {
-                                clean_up_timer = Duration::MAX;
-                                cache_table.inner.clean_up_interval.store(Arc::new(Duration::ZERO));
-                            } else {
-                                clean_up_timer = smallest_duration;
-                                cache_table.inner.clean_up_interval.store(Arc::new(smallest_duration));
-                            }
+                            clean_up_timer = match cache_table.clean_up() {
+                                Some(next) => next.saturating_duration_since(cache_table.now()),
+                                None => Duration::MAX,
+                            };
                         }
                         r = rx.recv() => {
                             if r.is_some() {
@@ -133,15 +191,208 @@ impl CacheTable {
         cache_table
     }
 
-    /// Return how many items are currently stored in the cache.
+    /// Returns the name of the table.
+    pub fn name(&self) -> &str {
+        &self.inner.name
+    }
+
+    /// Returns the maximum number of items, zero means unbounded.
+    pub fn capacity(&self) -> usize {
+        self.inner.capacity.load(Ordering::Relaxed)
+    }
+
+    /// Configures the maximum number of items, zero meaning unbounded.
+    ///
+    /// Items are evicted right away if the table holds more than the new capacity.
+    pub fn set_capacity(&mut self, capacity: usize) {
+        self.inner.capacity.store(capacity, Ordering::Relaxed);
+        let mut notifications = Notifications::default();
+        self.evict(
+            &mut self.inner.items.write().unwrap(),
+            None,
+            &mut notifications,
+        );
+        self.dispatch(notifications);
+    }
+
+    /// Returns the maximum total weight of all items, zero means unbounded.
+    pub fn max_weight(&self) -> usize {
+        self.inner.max_weight.load(Ordering::Relaxed)
+    }
+
+    /// Configures the maximum total weight of all items, zero meaning unbounded.
+    ///
+    /// Items are evicted right away if the table is over the new budget.
+    pub fn set_max_weight(&mut self, max_weight: usize) {
+        self.inner.max_weight.store(max_weight, Ordering::Relaxed);
+        let mut notifications = Notifications::default();
+        self.evict(
+            &mut self.inner.items.write().unwrap(),
+            None,
+            &mut notifications,
+        );
+        self.dispatch(notifications);
+    }
+
+    /// Returns the total weight of all items currently stored in the cache.
+    pub fn weight(&self) -> usize {
+        self.inner.items.read().unwrap().weight
+    }
+
+    /// Configures the weigher of items with key type K.
+    ///
+    /// Items without a weigher weigh 1. Already cached items keep their weight until they are updated.
+    pub fn set_weigher<K: 'static + TypedMap + Send + Sync + Clone>(
+        &mut self,
+        f: impl Fn(&K, &K::Value) -> usize + Send + Sync + 'static,
+    ) where
+        K::Value: Send + Sync,
+    {
+        self.inner.weighers.write().unwrap().insert(
+            TypeId::of::<K>(),
+            Box::new(move |key, value| {
+                match (key.downcast_ref::<K>(), value.typed_ref::<K::Value>()) {
+                    (Some(key), Some(value)) => f(key, &value),
+                    _ => 1,
+                }
+            }),
+        );
+    }
+
+    /// Configures the quota of items with key type K.
+    ///
+    /// Once the quota is exceeded, only items of type K are evicted. Items are evicted right away if the quota is already exceeded.
+    pub fn set_quota<K: 'static + TypedMap + Send + Sync + Clone>(&mut self, quota: Quota)
+    where
+        K::Value: Send + Sync,
+    {
+        self.inner
+            .quotas
+            .write()
+            .unwrap()
+            .insert(TypeId::of::<K>(), quota);
+        let mut notifications = Notifications::default();
+        self.evict_type(
+            &mut self.inner.items.write().unwrap(),
+            TypeId::of::<K>(),
+            None,
+            &mut notifications,
+        );
+        self.dispatch(notifications);
+    }
+
+    /// Returns the quota of items with key type K, if any.
+    pub fn quota<K: 'static + TypedMap + Send + Sync + Clone>(&self) -> Option<Quota>
+    where
+        K::Value: Send + Sync,
+    {
+        self.inner
+            .quotas
+            .read()
+            .unwrap()
+            .get(&TypeId::of::<K>())
+            .copied()
+    }
+
+    /// Returns the number and total weight of items with key type K currently stored in the cache.
+    ///
+    /// Stale items are included, as they take up room until their grace period ends.
+    pub fn usage<K: 'static + TypedMap + Send + Sync + Clone>(&self) -> Usage
+    where
+        K::Value: Send + Sync,
+    {
+        self.inner
+            .items
+            .read()
+            .unwrap()
+            .usage
+            .get(&TypeId::of::<K>())
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Returns the number and total weight of items currently stored in the cache, by key type, stale items included.
+    pub fn usage_by_type(&self) -> HashMap<TypeId, Usage> {
+        self.inner.items.read().unwrap().usage.clone()
+    }
+
+    /// Configures the Expiry computing custom deadlines of items with key type K.
+    ///
+    /// Already cached items keep their deadlines until they are read or updated.
+    pub fn set_expiry<K: 'static + TypedMap + Send + Sync + Clone>(
+        &mut self,
+        expiry: impl Expiry<K> + 'static,
+    ) where
+        K::Value: Send + Sync,
+    {
+        self.inner
+            .expiries
+            .write()
+            .unwrap()
+            .insert(TypeId::of::<K>(), Arc::new(TypedExpiry::new(expiry)));
+    }
+
+    fn expiry(&self, key: &TypedKey) -> Option<Arc<dyn ErasedExpiry>> {
+        self.inner
+            .expiries
+            .read()
+            .unwrap()
+            .get(&key.key_type_id())
+            .cloned()
+    }
+
+    /// Configures the clock used for timestamps and deadlines of items.
+    ///
+    /// It should be configured before any item is added, as cached items keep their timestamps.
+    pub fn set_clock(&mut self, clock: impl Clock + 'static) {
+        *self.inner.clock.write().unwrap() = Arc::new(clock);
+    }
+
+    /// Returns the current time according to the table's clock.
+    pub fn now(&self) -> Instant {
+        self.inner.clock.read().unwrap().now()
+    }
+
+    /// Returns a snapshot of the statistics of the table.
+    pub fn stats(&self) -> Stats {
+        self.inner.stats.snapshot()
+    }
+
+    /// Resets the statistics of the table.
+    pub fn reset_stats(&self) {
+        self.inner.stats.reset();
+    }
+
+    /// Returns a snapshot of the statistics of the table by key type.
+    ///
+    /// Every key type which has been looked up or stored in the table is reported.
+    pub fn stats_by_type(&self) -> HashMap<TypeId, TypeStats> {
+        let items = self.inner.items.read().unwrap();
+        let counts = items
+            .usage
+            .iter()
+            .map(|(type_id, usage)| {
+                let stale = items.stale.get(type_id).copied().unwrap_or_default();
+                (*type_id, usage.count - stale)
+            })
+            .collect();
+        self.inner.stats.snapshot_by_type(&counts)
+    }
+
+    pub(crate) fn counters(&self) -> &Counters {
+        &self.inner.stats
+    }
+
+    /// Return how many items are currently stored in the cache, not counting stale ones.
     pub fn count(&self) -> usize {
-        self.inner.items.read().unwrap().len()
+        let items = self.inner.items.read().unwrap();
+        items.len() - items.stale.values().sum::<usize>()
     }
 
-    /// Trans all items
+    /// Trans all items, skipping stale ones.
     pub fn foreach(&self, trans: impl Fn(&TypedKey, CacheItem)) {
         let items = self.inner.items.read().unwrap();
-        for (k, v) in items.iter() {
+        for (k, v) in items.iter().filter(|(_, v)| !v.is_stale()) {
             trans(k, v.clone());
         }
     }
@@ -151,7 +402,200 @@ impl CacheTable {
         &mut self,
         f: impl Fn(TypedKey) -> Option<CacheItem> + Send + Sync + 'static,
     ) {
-        *self.inner.load_data.write().unwrap() = Some(Box::new(f));
+        *self.inner.load_data.write().unwrap() = Some(Arc::new(move |key| Ok(f(key))));
+    }
+
+    /// Configures a fallible data-loader callback, which will be called when trying to access a non-existing key.
+    ///
+    /// Its errors are returned as Error::LoadFailed, while Ok(None) means the key does not exist.
+    pub fn set_try_data_loader<E: Into<BoxError>>(
+        &mut self,
+        f: impl Fn(TypedKey) -> Result<Option<CacheItem>, E> + Send + Sync + 'static,
+    ) {
+        *self.inner.load_data.write().unwrap() =
+            Some(Arc::new(move |key| f(key).map_err(Into::into)));
+    }
+
+    /// Configures an asynchronous data-loader callback, which will be called by value_async when trying to access a non-existing key.
+    ///
+    /// It takes precedence over the synchronous data-loader in value_async.
+    pub fn set_async_data_loader(
+        &mut self,
+        f: impl Fn(TypedKey) -> BoxFuture<'static, Option<CacheItem>> + Send + Sync + 'static,
+    ) {
+        *self.inner.load_data_async.write().unwrap() = Some(Arc::new(move |key| {
+            let load = f(key);
+            Box::pin(async move { Ok(load.await) })
+        }));
+    }
+
+    /// Configures a fallible asynchronous data-loader callback, which will be called by value_async when trying to access a non-existing key.
+    ///
+    /// Its errors are returned as Error::LoadFailed, while Ok(None) means the key does not exist.
+    pub fn set_try_async_data_loader<E: Into<BoxError> + 'static>(
+        &mut self,
+        f: impl Fn(TypedKey) -> BoxFuture<'static, Result<Option<CacheItem>, E>> + Send + Sync + 'static,
+    ) {
+        *self.inner.load_data_async.write().unwrap() = Some(Arc::new(move |key| {
+            let load = f(key);
+            Box::pin(async move { load.await.map_err(Into::into) })
+        }));
+    }
+
+    /// Configures a data-loader for keys of type K, which will be called when trying to access a non-existing key.
+    ///
+    /// It returns the value along with its life span, and takes precedence over the table-wide data-loaders.
+    pub fn set_loader<K: 'static + TypedMap + Send + Sync + Clone>(
+        &mut self,
+        f: impl Fn(&K) -> Option<(K::Value, Duration)> + Send + Sync + 'static,
+    ) where
+        K::Value: Send + Sync,
+    {
+        self.inner.loaders.write().unwrap().insert(
+            TypeId::of::<K>(),
+            Arc::new(move |key: TypedKey| {
+                let Some(key) = key.downcast_ref::<K>() else {
+                    return Ok(None);
+                };
+                Ok(f(key).map(|(value, life_span)| CacheItem::new(key.clone(), life_span, value)))
+            }),
+        );
+    }
+
+    /// Returns the data-loader for keys of type K, if any.
+    fn type_loader<K: 'static>(&self) -> Option<Loader> {
+        self.inner
+            .loaders
+            .read()
+            .unwrap()
+            .get(&TypeId::of::<K>())
+            .cloned()
+    }
+
+    /// Configures how long keys the data-loader could not find are remembered, zero meaning they are not.
+    ///
+    /// Until then, the value methods fail with Error::KeyNotFoundOrLoadable without calling the data-loader again.
+    /// Adding an item with such a key makes the table forget about the miss.
+    pub fn set_negative_time_to_live(&mut self, negative_time_to_live: Duration) {
+        self.inner
+            .negative_time_to_live
+            .store(Arc::new(negative_time_to_live));
+    }
+
+    /// Calls a data-loader and records the load in the statistics of the table.
+    fn load<T>(&self, f: impl FnOnce() -> Result<T, BoxError>) -> Result<T, BoxError> {
+        let start = Instant::now();
+        let result = f();
+        self.loaded(start, &result);
+        result
+    }
+
+    /// Records a load which started at the given time in the statistics of the table.
+    ///
+    /// Load times are measured with the system time rather than the clock of the table.
+    fn loaded<T>(&self, start: Instant, result: &Result<T, BoxError>) {
+        self.inner
+            .stats
+            .record_load(start.elapsed(), result.is_err());
+    }
+
+    /// Returns whether the data-loader recently could not find the given key.
+    fn is_known_miss(&self, key: &dyn Key) -> bool {
+        let mut misses = self.inner.misses.lock().unwrap();
+        match misses.get(key) {
+            Some(until) if *until > self.now() => true,
+            Some(_) => {
+                misses.remove(key);
+                false
+            }
+            None => false,
+        }
+    }
+
+    /// Remembers that the data-loader could not find the given key.
+    fn remember_miss(&self, key: TypedKey) {
+        let negative_time_to_live = **self.inner.negative_time_to_live.load();
+        if negative_time_to_live > Duration::ZERO {
+            let until = self.now() + negative_time_to_live;
+            self.inner.misses.lock().unwrap().insert(key, until);
+            self.wake_clean_up(Some(until));
+        }
+    }
+
+    /// Configures the age after which items are reloaded in the background when read, zero meaning never.
+    ///
+    /// Reads keep returning the current item while the data-loader runs, then the new item replaces it
+    /// and inherits its about_to_expire callbacks.
+    pub fn set_refresh_after(&mut self, refresh_after: Duration) {
+        self.inner.refresh_after.store(Arc::new(refresh_after));
+    }
+
+    /// Configures the age after which items with key type K are reloaded in the background when read.
+    ///
+    /// It takes precedence over the table-wide refresh age.
+    pub fn set_refresh_after_for<K: 'static + TypedMap + Send + Sync + Clone>(
+        &mut self,
+        refresh_after: Duration,
+    ) where
+        K::Value: Send + Sync,
+    {
+        self.inner
+            .refresh_after_by_type
+            .write()
+            .unwrap()
+            .insert(TypeId::of::<K>(), refresh_after);
+    }
+
+    /// Returns how long expired items are kept as stale.
+    pub fn grace_period(&self) -> Duration {
+        **self.inner.grace_period.load()
+    }
+
+    /// Configures how long expired items are kept as stale, zero meaning they are removed right away.
+    ///
+    /// Stale items are treated as missing, unless a lookup opts in to them via the value_or_stale method.
+    /// Their callbacks are triggered once the grace period is over.
+    pub fn set_grace_period(&mut self, grace_period: Duration) {
+        self.inner.grace_period.store(Arc::new(grace_period));
+    }
+
+    /// Configures a batch loader for keys of type K, which will be called by get_many with all missing keys at once.
+    ///
+    /// Parameter life_span is the life span of loaded items.
+    /// Parameter window determines how long get_many_async collects misses from concurrent tasks into a single batch, zero meaning no collection.
+    pub fn set_batch_loader<K: 'static + TypedMap + Send + Sync + Clone>(
+        &mut self,
+        life_span: Duration,
+        window: Duration,
+        f: impl Fn(Vec<K>) -> HashMap<K, K::Value> + Send + Sync + 'static,
+    ) where
+        K::Value: Send + Sync,
+    {
+        self.inner.batch_loaders.write().unwrap().insert(
+            TypeId::of::<K>(),
+            Arc::new(BatchLoader {
+                load: Box::new(f),
+                life_span,
+                window,
+                pending: Mutex::new(None),
+            }),
+        );
+    }
+
+    fn batch_loader<K: 'static + TypedMap + Send + Sync + Clone>(
+        &self,
+    ) -> Option<Arc<BatchLoader<K>>>
+    where
+        K::Value: Send + Sync,
+    {
+        self.inner
+            .batch_loaders
+            .read()
+            .unwrap()
+            .get(&TypeId::of::<K>())?
+            .clone()
+            .downcast()
+            .ok()
     }
 
     /// Configures a callback, which will be called when an item is added to the cache.
@@ -159,23 +603,42 @@ impl CacheTable {
         if !self.inner.added_item.read().unwrap().is_empty() {
             self.remove_added_item_callbacks();
         }
-        self.inner.added_item.write().unwrap().push(Box::new(f));
+        self.inner.added_item.write().unwrap().push(Arc::new(f));
     }
 
     /// Appends a new callback to the added_item queue.
     pub fn add_added_item_callback(&mut self, f: impl Fn(CacheItem) + Send + Sync + 'static) {
-        self.inner.added_item.write().unwrap().push(Box::new(f));
+        self.inner.added_item.write().unwrap().push(Arc::new(f));
+    }
+
+    /// Appends a new asynchronous callback to the added_item queue.
+    ///
+    /// The returned futures are run by a notification task of the table, see set_notification_concurrency.
+    pub fn add_added_item_callback_async<F>(
+        &mut self,
+        f: impl Fn(CacheItem) -> F + Send + Sync + 'static,
+    ) where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        self.inner
+            .added_item_async
+            .write()
+            .unwrap()
+            .push(Arc::new(move |item| Box::pin(f(item))));
     }
 
-    /// Removes all added_item callbacks.
+    /// Removes all added_item callbacks, including asynchronous ones.
     pub fn remove_added_item_callbacks(&mut self) {
         self.inner.added_item.write().unwrap().clear();
+        self.inner.added_item_async.write().unwrap().clear();
     }
 
     /// Configures a callback, which will be called when an item is about to be deleted from the cache.
+    ///
+    /// The callback receives the item along with the cause of its removal.
     pub fn set_about_to_delete_item_callback(
         &mut self,
-        f: impl Fn(CacheItem) + Send + Sync + 'static,
+        f: impl Fn(CacheItem, RemovalCause) + Send + Sync + 'static,
     ) {
         if !self.inner.about_to_delete_item.read().unwrap().is_empty() {
             self.remove_about_to_delete_item_callbacks();
@@ -184,23 +647,105 @@ impl CacheTable {
             .about_to_delete_item
             .write()
             .unwrap()
-            .push(Box::new(f));
+            .push(Arc::new(f));
     }
 
     pub fn add_about_to_delete_item_callback(
         &mut self,
-        f: impl Fn(CacheItem) + Send + Sync + 'static,
+        f: impl Fn(CacheItem, RemovalCause) + Send + Sync + 'static,
     ) {
         self.inner
             .about_to_delete_item
             .write()
             .unwrap()
-            .push(Box::new(f));
+            .push(Arc::new(f));
+    }
+
+    /// Appends a new asynchronous callback to the about_to_delete_item queue.
+    ///
+    /// The returned futures are run by a notification task of the table, see set_notification_concurrency.
+    pub fn add_about_to_delete_item_callback_async<F>(
+        &mut self,
+        f: impl Fn(CacheItem, RemovalCause) -> F + Send + Sync + 'static,
+    ) where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        self.inner
+            .about_to_delete_item_async
+            .write()
+            .unwrap()
+            .push(Arc::new(move |item, cause| Box::pin(f(item, cause))));
     }
 
-    /// Removes all about_to_delete_item callbacks.
+    /// Removes all about_to_delete_item callbacks, including asynchronous ones.
     pub fn remove_about_to_delete_item_callbacks(&mut self) {
         self.inner.about_to_delete_item.write().unwrap().clear();
+        self.inner
+            .about_to_delete_item_async
+            .write()
+            .unwrap()
+            .clear();
+    }
+
+    /// Returns a stream of the changes of the table, from now on.
+    ///
+    /// Each subscriber receives every event independently of callbacks and other subscribers.
+    /// A subscriber falling more than 1024 events behind misses the oldest ones and receives a lag error instead.
+    pub fn subscribe(&self) -> BroadcastStream<CacheEvent> {
+        BroadcastStream::new(self.inner.events.subscribe())
+    }
+
+    /// Appends a new callback to the added_item queue, which is only triggered for items with key type K.
+    ///
+    /// The callback receives the typed key and value of the added item.
+    pub fn on_added<K: 'static + TypedMap + Send + Sync + Clone>(
+        &mut self,
+        f: impl Fn(&K, &K::Value) + Send + Sync + 'static,
+    ) where
+        K::Value: Send + Sync,
+    {
+        self.add_added_item_callback(move |item| {
+            if let (Some(key), Some(value)) = (
+                item.key().downcast_ref::<K>(),
+                item.value().typed_ref::<K::Value>(),
+            ) {
+                f(key, &value);
+            }
+        });
+    }
+
+    /// Appends a new callback to the about_to_delete_item queue, which is only triggered for items with key type K.
+    ///
+    /// The callback receives the typed key and value of the removed item, along with the cause of its removal.
+    pub fn on_removed<K: 'static + TypedMap + Send + Sync + Clone>(
+        &mut self,
+        f: impl Fn(&K, &K::Value, RemovalCause) + Send + Sync + 'static,
+    ) where
+        K::Value: Send + Sync,
+    {
+        self.add_about_to_delete_item_callback(move |item, cause| {
+            if let (Some(key), Some(value)) = (
+                item.key().downcast_ref::<K>(),
+                item.value().typed_ref::<K::Value>(),
+            ) {
+                f(key, &value, cause);
+            }
+        });
+    }
+
+    /// Configures how many futures of asynchronous callbacks may run at once, one by default.
+    ///
+    /// Futures are started in the order of the changes they report, so with a concurrency of one
+    /// each future completes before the next one starts.
+    pub fn set_notification_concurrency(&mut self, concurrency: usize) {
+        self.inner.notifier.set_concurrency(concurrency);
+    }
+
+    /// Waits until the futures of all asynchronous callbacks triggered so far have completed.
+    ///
+    /// This is useful to flush pending notifications during shutdown.
+    pub async fn wait_for_notifications(&self) {
+        self.inner.notifier.wait().await;
     }
 
     /// Adds a key/value pair to the cache.
@@ -221,6 +766,26 @@ impl CacheTable {
         self.add_internal(key, item)
     }
 
+    /// Adds a key/value pair to the cache which also expires a fixed time after its creation.
+    ///
+    /// Parameter key is the item's cache-key.
+    /// Parameter life_span determines after which time period without an access the item will get removed from the cache.
+    /// Parameter time_to_live determines after which time period since its creation the item will get removed from the cache, even if it is being accessed.
+    /// Parameter value is the item's value.
+    pub fn add_with_time_to_live<K: 'static + TypedMap + Send + Sync + Clone>(
+        &self,
+        key: K,
+        life_span: Duration,
+        time_to_live: Duration,
+        value: K::Value,
+    ) -> Option<CacheItem>
+    where
+        K::Value: Send + Sync,
+    {
+        let item = CacheItem::with_time_to_live(key.clone(), life_span, time_to_live, value);
+        self.add_internal(key, item)
+    }
+
     fn add_internal<K: 'static + TypedMap + Send + Sync + Clone>(
         &self,
         key: K,
@@ -234,39 +799,554 @@ impl CacheTable {
             item.life_span(),
             self.inner.name
         );
-        let ret = self
-            .inner
-            .items
-            .write()
-            .unwrap()
-            .insert(TypedKey::from_key(key), item.clone());
+        let mut notifications = Notifications::default();
+        let ret = self.insert_item(
+            &mut self.inner.items.write().unwrap(),
+            TypedKey::from_key(key),
+            item,
+            &mut notifications,
+        );
+        self.dispatch(notifications);
 
-        {
-            let added_item = self.inner.added_item.read().unwrap();
-            if !added_item.is_empty() {
-                for callback in added_item.iter() {
-                    callback(item.clone());
-                }
-            }
-        }
+        ret
+    }
 
-        let exp_dur = self.inner.clean_up_interval.load();
-        if item.life_span() > Duration::ZERO
-            && (**exp_dur == Duration::ZERO || item.life_span() < **exp_dur)
+    /// Inserts an item into the locked items and evicts others until the table is within its bounds.
+    ///
+    /// Returns the replaced item. The changes are queued to the given notifications.
+    pub(crate) fn insert_item(
+        &self,
+        items: &mut Items,
+        key: TypedKey,
+        item: CacheItem,
+        notifications: &mut Notifications,
+    ) -> Option<CacheItem> {
+        let now = self.now();
+        item.stamp(now);
+        item.set_weight(self.weigh(&key, item.value()));
+        if let Some(expiry) = self.expiry(&key) {
+            // A stale item has already expired, so its replacement starts afresh.
+            let deadline = match items.get(&key as &dyn Key).filter(|old| !old.is_stale()) {
+                Some(old) => expiry
+                    .expire_after_update(
+                        &key,
+                        item.value(),
+                        now,
+                        remaining(old.custom_deadline(), now),
+                    )
+                    .map(|d| now + d),
+                None => expiry
+                    .expire_after_create(&key, item.value(), now)
+                    .map(|d| now + d),
+            };
+            item.set_custom_deadline(deadline);
+        }
         {
-            match self.inner.tx.send(()) {
-                Ok(_) => {}
-                Err(e) => {
-                    tracing::error!("Error sending to channel for clean_up: {}", e);
-                }
+            let mut misses = self.inner.misses.lock().unwrap();
+            if !misses.is_empty() {
+                misses.remove(&key as &dyn Key);
             }
         }
-
+        let type_id = key.key_type_id();
+        self.inner.stats.record_insertion(KeyType::of_key(&key));
+        let ret = items.insert(key, item.clone());
+        match &ret {
+            Some(old) => notifications.replaced(old.clone(), item.clone()),
+            None => notifications.added(item.clone()),
+        }
+        self.evict_type(items, type_id, Some(&item), notifications);
+        self.evict(items, Some(&item), notifications);
         ret
     }
 
+    fn weigh(&self, key: &TypedKey, value: &TypedValue) -> usize {
+        match self.inner.weighers.read().unwrap().get(&key.key_type_id()) {
+            Some(weigher) => weigher(key, value),
+            None => 1,
+        }
+    }
+
+    /// Evicts items, except the spared one, until the table is within its capacity and weight budget.
+    fn evict(
+        &self,
+        items: &mut Items,
+        spare: Option<&CacheItem>,
+        notifications: &mut Notifications,
+    ) {
+        loop {
+            let capacity = self.inner.capacity.load(Ordering::Relaxed);
+            let max_weight = self.inner.max_weight.load(Ordering::Relaxed);
+            if (capacity == 0 || items.len() <= capacity)
+                && (max_weight == 0 || items.weight <= max_weight)
+            {
+                break;
+            }
+            let victim = self.evict_one(items, spare, None).or_else(|| {
+                // The spared item alone does not fit either, so it goes too.
+                (items.len() == 1)
+                    .then(|| self.evict_spared(items, spare))
+                    .flatten()
+            });
+            match victim {
+                Some(item) => notifications.removed(item, RemovalCause::Evicted),
+                None => break,
+            }
+        }
+    }
+
+    /// Evicts items of the given key type, except the spared one, until they are within their quota.
+    fn evict_type(
+        &self,
+        items: &mut Items,
+        type_id: TypeId,
+        spare: Option<&CacheItem>,
+        notifications: &mut Notifications,
+    ) {
+        let Some(quota) = self.inner.quotas.read().unwrap().get(&type_id).copied() else {
+            return;
+        };
+        while items
+            .usage
+            .get(&type_id)
+            .is_some_and(|usage| quota.is_exceeded_by(usage))
+        {
+            let victim = self.evict_one(items, spare, Some(type_id)).or_else(|| {
+                (items
+                    .usage
+                    .get(&type_id)
+                    .is_some_and(|usage| usage.count == 1))
+                .then(|| {
+                    self.evict_spared(
+                        items,
+                        spare.filter(|spare| spare.key().key_type_id() == type_id),
+                    )
+                })
+                .flatten()
+            });
+            match victim {
+                Some(item) => notifications.removed(item, RemovalCause::Evicted),
+                None => break,
+            }
+        }
+    }
+
+    /// Evicts the spared item, if it is still cached.
+    fn evict_spared(&self, items: &mut Items, spare: Option<&CacheItem>) -> Option<CacheItem> {
+        let spare = spare.filter(|spare| {
+            items
+                .get(spare.key() as &dyn Key)
+                .is_some_and(|i| i.ptr_eq(spare))
+        })?;
+        tracing::trace!(
+            "Evicting item weighing {} which does not fit into table {}",
+            spare.weight(),
+            self.inner.name
+        );
+        self.inner.stats.record_eviction();
+        items.remove(spare.key() as &dyn Key)
+    }
+
+    /// Evicts the item selected by the eviction policy, optionally among the items of a single key type.
+    fn evict_one(
+        &self,
+        items: &mut Items,
+        spare: Option<&CacheItem>,
+        type_id: Option<TypeId>,
+    ) -> Option<CacheItem> {
+        let victim = if items.ranked {
+            items.next_victim(spare, type_id)?
+        } else {
+            self.select_victim(items, spare, type_id)?
+        };
+        tracing::trace!(
+            "Evicting item created on {:?} and hit {} times from table {}",
+            victim.created_on(),
+            victim.access_count(),
+            self.inner.name
+        );
+        self.inner.stats.record_eviction();
+        items.remove(victim.key() as &dyn Key)
+    }
+
+    /// Selects the item to evict among all items, for policies which do not rank them.
+    fn select_victim(
+        &self,
+        items: &Items,
+        spare: Option<&CacheItem>,
+        type_id: Option<TypeId>,
+    ) -> Option<CacheItem> {
+        let candidate = |i: &&CacheItem| {
+            spare.is_none_or(|spare| !i.ptr_eq(spare))
+                && type_id.is_none_or(|type_id| i.key().key_type_id() == type_id)
+        };
+        // Stale items go first, as they are only kept around as a fallback.
+        let stale = (!items.stale.is_empty())
+            .then(|| {
+                items
+                    .values()
+                    .filter(candidate)
+                    .filter(|i| i.is_stale())
+                    .min_by_key(|i| i.expires_at())
+            })
+            .flatten();
+        let victim = match stale {
+            Some(victim) => victim,
+            None => self
+                .inner
+                .eviction_policy
+                .select_victim(&mut items.values().filter(candidate))?,
+        };
+        Some(victim.clone())
+    }
+
+    /// Recomputes the weight and deadline of a cached item after its value changed.
+    fn updated(&self, item: &CacheItem) {
+        let mut notifications = Notifications::default();
+        {
+            let mut items = self.inner.items.write().unwrap();
+            if !items
+                .get(item.key() as &dyn Key)
+                .is_some_and(|i| i.ptr_eq(item))
+            {
+                return;
+            }
+            self.modified(&mut items, item, &mut notifications);
+        }
+        self.wake_clean_up(item.expires_at());
+        self.dispatch(notifications);
+    }
+
+    /// Recomputes the weight and deadline of a cached item after its value changed, evicting others if needed.
+    ///
+    /// The caller must hold the lock and dispatch the notifications and wake the clean up once it is released.
+    pub(crate) fn modified(
+        &self,
+        items: &mut Items,
+        item: &CacheItem,
+        notifications: &mut Notifications,
+    ) {
+        let previous = item.expires_at();
+        if let Some(expiry) = self.expiry(item.key()) {
+            let now = self.now();
+            let duration = expiry.expire_after_update(
+                item.key(),
+                item.value(),
+                now,
+                remaining(item.custom_deadline(), now),
+            );
+            item.set_custom_deadline(duration.map(|d| now + d));
+        }
+        let weight = self.weigh(item.key(), item.value());
+        items.reweigh(item, weight);
+        items.reschedule(item, previous);
+        self.evict_type(items, item.key().key_type_id(), Some(item), notifications);
+        self.evict(items, Some(item), notifications);
+    }
+
+    /// Marks a cached item to be kept alive after it has been read, and refreshes it if it is due.
+    fn hit<K: 'static + TypedMap + Send + Sync + Clone>(&self, key: &K, item: &CacheItem)
+    where
+        K::Value: Send + Sync,
+    {
+        let now = self.now();
+        item.keep_alive_at(now);
+        self.read(item);
+        self.inner.stats.record_hit(KeyType::of::<K>());
+
+        let refresh_after = self
+            .inner
+            .refresh_after_by_type
+            .read()
+            .unwrap()
+            .get(&TypeId::of::<K>())
+            .copied()
+            .unwrap_or_else(|| **self.inner.refresh_after.load());
+        if refresh_after == Duration::ZERO
+            || now.saturating_duration_since(item.created_on()) < refresh_after
+        {
+            return;
+        }
+        self.refresh(key.clone(), item.clone());
+    }
+
+    /// Reloads an item in the background and swaps the new item in.
+    fn refresh<K: 'static + TypedMap + Send + Sync + Clone>(&self, key: K, item: CacheItem)
+    where
+        K::Value: Send + Sync,
+    {
+        let Ok(handle) = tokio::runtime::Handle::try_current() else {
+            return;
+        };
+        let (load_data_async, load_data) = match self.type_loader::<K>() {
+            Some(loader) => (None, Some(loader)),
+            None => (
+                self.inner.load_data_async.read().unwrap().clone(),
+                self.inner.load_data.read().unwrap().clone(),
+            ),
+        };
+        if (load_data_async.is_none() && load_data.is_none()) || !item.start_refresh() {
+            return;
+        }
+        tracing::trace!(
+            "Refreshing item created on {:?} in table {}",
+            item.created_on(),
+            self.inner.name
+        );
+        let cache_table = self.clone();
+        let typed_key = TypedKey::from_key(key.clone());
+        match (load_data_async, load_data) {
+            (Some(load_data_async), _) => {
+                handle.spawn(async move {
+                    let start = Instant::now();
+                    let result = load_data_async(typed_key).await;
+                    cache_table.loaded(start, &result);
+                    cache_table.refreshed(key, &item, result);
+                });
+            }
+            (None, Some(load_data)) => {
+                handle.spawn_blocking(move || {
+                    let result = cache_table.load(|| load_data(typed_key));
+                    cache_table.refreshed(key, &item, result);
+                });
+            }
+            (None, None) => item.end_refresh(),
+        }
+    }
+
+    /// Swaps a reloaded item in, unless the old one has been removed or replaced in the meantime.
+    ///
+    /// If the key no longer exists according to the data-loader, the old item is deleted.
+    fn refreshed<K: 'static + TypedMap + Send + Sync + Clone>(
+        &self,
+        key: K,
+        old: &CacheItem,
+        result: Result<Option<CacheItem>, BoxError>,
+    ) where
+        K::Value: Send + Sync,
+    {
+        let new = match result {
+            Ok(new) => new,
+            Err(e) => {
+                tracing::warn!("Refreshing item in table {} failed: {}", self.inner.name, e);
+                old.end_refresh();
+                return;
+            }
+        };
+        let mut items = self.inner.items.write().unwrap();
+        if !items
+            .get(old.key() as &dyn Key)
+            .is_some_and(|i| i.ptr_eq(old))
+        {
+            return;
+        }
+        let mut notifications = Notifications::default();
+        match new {
+            Some(new) => {
+                new.inherit_about_to_expire_callbacks(old);
+                new.inherit_access_count(old);
+                self.insert_item(&mut items, TypedKey::from_key(key), new, &mut notifications);
+            }
+            None => {
+                // The data-loader no longer knows the key, so the item expires early.
+                items.remove(old.key() as &dyn Key);
+                self.inner
+                    .stats
+                    .record_expiration(KeyType::of_key(old.key()));
+                notifications.removed(old.clone(), RemovalCause::Expired);
+            }
+        }
+        drop(items);
+        self.dispatch(notifications);
+    }
+
+    /// Recomputes the deadline of a cached item after it has been read.
+    fn read(&self, item: &CacheItem) {
+        let Some(expiry) = self.expiry(item.key()) else {
+            return;
+        };
+        let previous = item.expires_at();
+        let now = self.now();
+        let duration = expiry.expire_after_read(
+            item.key(),
+            item.value(),
+            now,
+            remaining(item.custom_deadline(), now),
+        );
+        item.set_custom_deadline(duration.map(|d| now + d));
+        // A later deadline is picked up once the previous one is due, so only an earlier one needs the lock.
+        let deadline = item.expires_at();
+        if deadline.is_none_or(|at| previous.is_some_and(|previous| at >= previous)) {
+            return;
+        }
+        {
+            let mut items = self.inner.items.write().unwrap();
+            if items
+                .get(item.key() as &dyn Key)
+                .is_some_and(|i| i.ptr_eq(item))
+            {
+                items.reschedule(item, previous);
+            }
+        }
+        self.wake_clean_up(deadline);
+    }
+
+    /// Triggers the callbacks of the queued notifications in order.
+    ///
+    /// This must be called once the items are unlocked, so that callbacks may access the table.
+    pub(crate) fn dispatch(&self, notifications: Notifications) {
+        for notification in notifications.0 {
+            match notification {
+                Notification::Added(item) => {
+                    self.notify_added(&item);
+                    self.publish(|| CacheEvent::Added(item));
+                }
+                Notification::Replaced(old, new) => {
+                    self.notify_removed(&old, RemovalCause::Replaced);
+                    self.notify_added(&new);
+                    self.publish(|| CacheEvent::Replaced { old, new });
+                }
+                Notification::Removed(item, cause) => {
+                    self.notify_removed(&item, cause);
+                    self.publish(|| CacheEvent::Removed(item, cause));
+                }
+                Notification::Flushed(items) => {
+                    for item in &items {
+                        self.notify_removed(item, RemovalCause::Flushed);
+                    }
+                    self.publish(|| CacheEvent::Flushed);
+                }
+            }
+        }
+    }
+
+    /// Sends an event to the subscribers of the table, if any.
+    fn publish(&self, event: impl FnOnce() -> CacheEvent) {
+        if self.inner.events.receiver_count() > 0 {
+            // Sending only fails if all subscribers went away in the meantime.
+            _ = self.inner.events.send(event());
+        }
+    }
+
+    /// Triggers the added_item callbacks and reschedules the clean up if the item expires earlier.
+    fn notify_added(&self, item: &CacheItem) {
+        // Callbacks are cloned so that they may register callbacks themselves.
+        let added_item = self.inner.added_item.read().unwrap().clone();
+        for callback in added_item.iter() {
+            callback(item.clone());
+        }
+        let added_item_async = self.inner.added_item_async.read().unwrap().clone();
+        for callback in added_item_async.iter() {
+            self.inner.notifier.notify(callback(item.clone()));
+        }
+
+        self.wake_clean_up(item.expires_at());
+    }
+
+    /// Triggers the clean up if the given deadline is earlier than the next scheduled one.
+    pub(crate) fn wake_clean_up(&self, deadline: Option<Instant>) {
+        let next_clean_up = self.inner.next_clean_up.load();
+        if let Some(deadline) = deadline {
+            if next_clean_up.is_none_or(|next| deadline < next) {
+                match self.inner.tx.send(()) {
+                    Ok(_) => {}
+                    Err(e) => {
+                        tracing::error!("Error sending to channel for clean_up: {}", e);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Removes all expired items right away.
+    ///
+    /// Expiration is driven by a background task, this is useful after advancing a mock clock.
+    /// Only the items whose deadline has passed are visited, so the cost is proportional to the
+    /// number of expiring items rather than to the size of the table.
+    /// Misses remembered for the negative time to live are forgotten once they are due as well.
+    /// Returns when the next item is due to expire.
+    pub fn clean_up(&self) -> Option<Instant> {
+        let (expired, next) = {
+            let mut items = self.inner.items.write().unwrap();
+            let now = self.now();
+            let expired = items.expire(now, self.grace_period());
+            let mut next = items.next_deadline();
+            self.inner.misses.lock().unwrap().retain(|_, until| {
+                if *until <= now {
+                    return false;
+                }
+                next = Some(next.map_or(*until, |next| next.min(*until)));
+                true
+            });
+            self.inner.next_clean_up.store(Arc::new(next));
+            (expired, next)
+        };
+        let mut notifications = Notifications::default();
+        for item in expired {
+            self.inner
+                .stats
+                .record_expiration(KeyType::of_key(item.key()));
+            tracing::trace!(
+                "Expiring item created on {:?} and hit {} times from table {}",
+                item.created_on(),
+                item.access_count(),
+                self.inner.name
+            );
+            notifications.removed(item, RemovalCause::Expired);
+        }
+        self.dispatch(notifications);
+        next
+    }
+
+    /// Triggers the about_to_delete_item callbacks of the table and the about_to_expire callbacks of the item.
+    fn notify_removed(&self, item: &CacheItem, cause: RemovalCause) {
+        let about_to_delete_item = self.inner.about_to_delete_item.read().unwrap().clone();
+        for callback in about_to_delete_item.iter() {
+            callback(item.clone(), cause);
+        }
+        let about_to_delete_item_async = self
+            .inner
+            .about_to_delete_item_async
+            .read()
+            .unwrap()
+            .clone();
+        for callback in about_to_delete_item_async.iter() {
+            self.inner.notifier.notify(callback(item.clone(), cause));
+        }
+
+        let about_to_expire = item.inner.about_to_expire.read().unwrap().clone();
+        for callback in about_to_expire.iter() {
+            callback(item.key(), cause);
+        }
+    }
+
     /// Returns the value of the item with the given key.
+    ///
+    /// The lookup is recorded as a hit or a miss in the statistics of the table.
     pub fn get<K: 'static + TypedMap + Send + Sync + Clone>(&self, key: &K) -> Option<CacheItem>
+    where
+        K::Value: Send + Sync,
+    {
+        let item = self.peek(key);
+        match item {
+            Some(_) => self.inner.stats.record_hit(KeyType::of::<K>()),
+            None => self.inner.stats.record_misses(KeyType::of::<K>(), 1),
+        }
+        item
+    }
+
+    /// Returns the item with the given key unless it is stale, without recording the lookup.
+    fn peek<K: 'static + TypedMap + Send + Sync + Clone>(&self, key: &K) -> Option<CacheItem>
+    where
+        K::Value: Send + Sync,
+    {
+        self.get_or_stale(key).filter(|item| !item.is_stale())
+    }
+
+    /// Returns the item with the given key, even if it is stale.
+    fn get_or_stale<K: 'static + TypedMap + Send + Sync + Clone>(
+        &self,
+        key: &K,
+    ) -> Option<CacheItem>
     where
         K::Value: Send + Sync,
     {
@@ -279,7 +1359,68 @@ impl CacheTable {
             .cloned()
     }
 
+    /// Returns the typed value of the item with the given key.
+    ///
+    /// Like the get method, this neither tries to fetch data via the loadData callback nor does it keep the item alive in the cache.
+    pub fn get_value<K: 'static + TypedMap + Send + Sync + Clone>(
+        &self,
+        key: &K,
+    ) -> Option<TypedRef<K::Value>>
+    where
+        K::Value: Send + Sync,
+    {
+        self.get(key)?.value().typed_ref::<K::Value>()
+    }
+
+    /// Returns the typed value of the item with the given key as a shared pointer.
+    pub fn get_value_arc<K: 'static + TypedMap + Send + Sync + Clone>(
+        &self,
+        key: &K,
+    ) -> Option<Arc<K::Value>>
+    where
+        K::Value: Send + Sync,
+    {
+        self.get(key)?.value().downcast_arc::<K::Value>()
+    }
+
+    /// Mutates the value of the item with the given key in place.
+    ///
+    /// The item keeps its metadata, such as creation time, access count and callbacks.
+    /// The value is only cloned if snapshots of it, e.g. from get_value, are still held, so that they stay unchanged.
+    pub fn update<K: 'static + TypedMap + Send + Sync + Clone, R>(
+        &self,
+        key: &K,
+        f: impl FnOnce(&mut K::Value) -> R,
+    ) -> Result<R, Error>
+    where
+        K::Value: Send + Sync + Clone,
+    {
+        let item = self.peek(key).ok_or(Error::KeyNotFound)?;
+        let ret = item
+            .value()
+            .update::<K::Value, R>(f)
+            .ok_or(Error::ValueTypeMismatch)?;
+        self.updated(&item);
+        Ok(ret)
+    }
+
+    /// Mutates the value of the item with the given key in place with a fallible closure.
+    ///
+    /// Changes made by the closure before it fails are kept.
+    pub fn try_update<K: 'static + TypedMap + Send + Sync + Clone, R, E: From<Error>>(
+        &self,
+        key: &K,
+        f: impl FnOnce(&mut K::Value) -> Result<R, E>,
+    ) -> Result<R, E>
+    where
+        K::Value: Send + Sync + Clone,
+    {
+        self.update(key, f)?
+    }
+
     /// Deletes the item with the given key from the cache.
+    ///
+    /// A stale item is removed as expired and reported as not found, like by get.
     pub fn delete<K: 'static + TypedMap + Send + Sync + Clone>(
         &self,
         key: &K,
@@ -298,56 +1439,49 @@ impl CacheTable {
         K::Value: Send + Sync,
     {
         let typed_key_ref = TypedKeyRef::from_key_ref(key);
-        if let Some(item) = self
+        let item = self
             .inner
             .items
             .write()
             .unwrap()
-            .remove(&typed_key_ref as &dyn Key)
-        {
-            tracing::trace!(
-                "Deleting item created on {:?} and hit {} times from table {}",
-                item.created_on(),
-                item.access_count(),
-                self.inner.name
-            );
-
-            {
-                let about_to_delete_item = self.inner.about_to_delete_item.read().unwrap();
-                if !about_to_delete_item.is_empty() {
-                    for callback in about_to_delete_item.iter() {
-                        callback(item.clone());
-                    }
-                }
+            .remove(&typed_key_ref as &dyn Key);
+        match item {
+            // A stale item is missing to callers, deleting it only cuts its grace period short.
+            Some(item) if item.is_stale() => {
+                self.inner
+                    .stats
+                    .record_expiration(KeyType::of_key(item.key()));
+                let mut notifications = Notifications::default();
+                notifications.removed(item, RemovalCause::Expired);
+                self.dispatch(notifications);
+                Err(Error::KeyNotFound)
             }
-
-            {
-                let about_to_expire = item.inner.about_to_expire.read().unwrap();
-                if !about_to_expire.is_empty() {
-                    for callback in about_to_expire.iter() {
-                        callback(item.key());
-                    }
-                }
+            Some(item) => {
+                self.inner.stats.record_deletion();
+                tracing::trace!(
+                    "Deleting item created on {:?} and hit {} times from table {}",
+                    item.created_on(),
+                    item.access_count(),
+                    self.inner.name
+                );
+                let mut notifications = Notifications::default();
+                notifications.removed(item.clone(), RemovalCause::Explicit);
+                self.dispatch(notifications);
+                Ok(item)
             }
-
-            Ok(item)
-        } else {
-            Err(Error::KeyNotFound)
+            None => Err(Error::KeyNotFound),
         }
     }
 
     /// Returns whether an item exists in the cache.
     ///
     /// Unlike the value method, exists neither tries to fetch data via the loadData callback nor does it keep the item alive in the cache.
+    /// It is not recorded as a hit or a miss either.
     pub fn exists<K: 'static + TypedMap + Send + Sync + Clone>(&self, key: K) -> bool
     where
         K::Value: Send + Sync,
     {
-        self.inner
-            .items
-            .read()
-            .unwrap()
-            .contains_key(&TypedKey::from_key(key))
+        self.peek(&key).is_some()
     }
 
     /// Checks whether an item is not yet cached.
@@ -362,18 +1496,31 @@ impl CacheTable {
     where
         K::Value: Send + Sync,
     {
-        if self
-            .inner
-            .items
-            .write()
-            .unwrap()
-            .contains_key(&TypedKey::from_key(key.clone()))
-        {
-            return false;
+        match self.entry(key) {
+            Entry::Occupied(_) => false,
+            Entry::Vacant(entry) => {
+                entry.insert(life_span, value);
+                true
+            }
+        }
+    }
+
+    /// Gets the given key's corresponding entry in the cache for in-place manipulation.
+    ///
+    /// The table stays locked until the returned entry is consumed or dropped.
+    pub fn entry<K: 'static + TypedMap + Send + Sync + Clone>(&self, key: K) -> Entry<'_, K>
+    where
+        K::Value: Send + Sync,
+    {
+        let items = self.inner.items.write().unwrap();
+        let item = items
+            .get(&TypedKeyRef::from_key_ref(&key) as &dyn Key)
+            .filter(|item| !item.is_stale())
+            .cloned();
+        match item {
+            Some(item) => Entry::Occupied(OccupiedEntry::new(self, items, key, item)),
+            None => Entry::Vacant(VacantEntry::new(self, items, key)),
         }
-        let item = CacheItem::new(key.clone(), life_span, value);
-        self.add_internal(key, item);
-        true
     }
 
     /// Returns an item from the cache and marks it to be kept alive.
@@ -385,29 +1532,748 @@ impl CacheTable {
         K::Value: Send + Sync,
     {
         let typed_key = TypedKey::from_key(key.clone());
-        let items = self.inner.items.read().unwrap();
-        if let Some(item) = items.get(&typed_key) {
-            item.keep_alive();
-            Ok(item.clone())
+        let item = self
+            .inner
+            .items
+            .read()
+            .unwrap()
+            .get(&typed_key as &dyn Key)
+            .filter(|item| !item.is_stale())
+            .cloned();
+        if let Some(item) = item {
+            self.hit(&key, &item);
+            Ok(item)
         } else {
-            drop(items);
-            let load_data = self.inner.load_data.read().unwrap();
-            if let Some(load_data) = load_data.as_ref() {
-                if let Some(item) = load_data(typed_key) {
-                    self.add_internal(key, item.clone());
-                    return Ok(item);
+            self.inner.stats.record_misses(KeyType::of::<K>(), 1);
+            let load_data = self
+                .type_loader::<K>()
+                .or_else(|| self.inner.load_data.read().unwrap().clone());
+            if let Some(load_data) = load_data {
+                if self.is_known_miss(&typed_key as &dyn Key) {
+                    return Err(Error::KeyNotFoundOrLoadable);
+                }
+                match self.load(|| load_data(typed_key)) {
+                    Ok(Some(item)) => {
+                        self.add_internal(key, item.clone());
+                        Ok(item)
+                    }
+                    Ok(None) => {
+                        self.remember_miss(TypedKey::from_key(key));
+                        Err(Error::KeyNotFoundOrLoadable)
+                    }
+                    Err(e) => Err(Error::LoadFailed(e.into())),
                 }
-                Err(Error::KeyNotFoundOrLoadable)
             } else {
                 Err(Error::KeyNotFound)
             }
         }
     }
 
+    /// Returns an item from the cache like the value method, or a stale item according to the given mode.
+    ///
+    /// With StaleMode::WhileRevalidate a stale item is reloaded in the background, which requires a tokio runtime.
+    /// With StaleMode::IfError it is reloaded right away and only returned if the data-loader fails.
+    pub fn value_or_stale<K: 'static + TypedMap + Send + Sync + Clone>(
+        &self,
+        key: K,
+        mode: StaleMode,
+    ) -> Result<CacheItem, Error>
+    where
+        K::Value: Send + Sync,
+    {
+        match (self.get_or_stale(&key), mode) {
+            (Some(item), StaleMode::WhileRevalidate) if item.is_stale() => {
+                self.inner.stats.record_hit(KeyType::of::<K>());
+                self.refresh(key, item.clone());
+                Ok(item)
+            }
+            (Some(item), StaleMode::IfError) if item.is_stale() => match self.value(key) {
+                Err(Error::LoadFailed(e)) => {
+                    tracing::warn!("Serving stale item from table {}: {}", self.inner.name, e);
+                    Ok(item)
+                }
+                result => result,
+            },
+            _ => self.value(key),
+        }
+    }
+
+    /// Returns an item from the cache like the value_async method, or a stale item according to the given mode.
+    pub async fn value_or_stale_async<K: 'static + TypedMap + Send + Sync + Clone>(
+        &self,
+        key: K,
+        mode: StaleMode,
+    ) -> Result<CacheItem, Error>
+    where
+        K::Value: Send + Sync,
+    {
+        match (self.get_or_stale(&key), mode) {
+            (Some(item), StaleMode::WhileRevalidate) if item.is_stale() => {
+                self.inner.stats.record_hit(KeyType::of::<K>());
+                self.refresh(key, item.clone());
+                Ok(item)
+            }
+            (Some(item), StaleMode::IfError) if item.is_stale() => {
+                match self.value_async(key).await {
+                    Err(Error::LoadFailed(e)) => {
+                        tracing::warn!("Serving stale item from table {}: {}", self.inner.name, e);
+                        Ok(item)
+                    }
+                    result => result,
+                }
+            }
+            _ => self.value_async(key).await,
+        }
+    }
+
+    /// Returns an item from the cache and marks it to be kept alive, loading it asynchronously on a miss.
+    ///
+    /// Concurrent misses for the same key wait on a single load instead of each calling the data-loader.
+    pub async fn value_async<K: 'static + TypedMap + Send + Sync + Clone>(
+        &self,
+        key: K,
+    ) -> Result<CacheItem, Error>
+    where
+        K::Value: Send + Sync,
+    {
+        if let Some(item) = self.peek(&key) {
+            self.hit(&key, &item);
+            return Ok(item);
+        }
+        self.inner.stats.record_misses(KeyType::of::<K>(), 1);
+
+        let (load_data_async, load_data) = match self.type_loader::<K>() {
+            Some(loader) => (None, Some(loader)),
+            None => (
+                self.inner.load_data_async.read().unwrap().clone(),
+                self.inner.load_data.read().unwrap().clone(),
+            ),
+        };
+        if load_data_async.is_none() && load_data.is_none() {
+            return Err(Error::KeyNotFound);
+        }
+        if self.is_known_miss(&TypedKeyRef::from_key_ref(&key) as &dyn Key) {
+            return Err(Error::KeyNotFoundOrLoadable);
+        }
+
+        let flight = self
+            .inner
+            .loading
+            .lock()
+            .unwrap()
+            .entry(TypedKey::from_key(key.clone()))
+            .or_default()
+            .clone();
+        let item = flight
+            .get_or_init(|| async {
+                // A previous load may have completed right before this one was registered.
+                if let Some(item) = self.peek(&key) {
+                    return Ok(Some(item));
+                }
+                let typed_key = TypedKey::from_key(key.clone());
+                let start = Instant::now();
+                let item = match (load_data_async, load_data) {
+                    (Some(load_data_async), _) => load_data_async(typed_key).await,
+                    // Sync loaders may block, so they must not run on a worker of the runtime.
+                    (None, Some(load_data)) => {
+                        tokio::task::spawn_blocking(move || load_data(typed_key))
+                            .await
+                            .unwrap_or_else(|e| Err(e.into()))
+                    }
+                    (None, None) => Ok(None),
+                };
+                self.loaded(start, &item);
+                let item = item.map_err(|e| Error::LoadFailed(e.into()))?;
+                match &item {
+                    Some(item) => {
+                        self.add_internal(key.clone(), item.clone());
+                    }
+                    None => self.remember_miss(TypedKey::from_key(key.clone())),
+                }
+                Ok(item)
+            })
+            .await
+            .clone();
+
+        {
+            let mut loading = self.inner.loading.lock().unwrap();
+            let typed_key_ref = TypedKeyRef::from_key_ref(&key);
+            if loading
+                .get(&typed_key_ref as &dyn Key)
+                .is_some_and(|f| Arc::ptr_eq(f, &flight))
+            {
+                loading.remove(&typed_key_ref as &dyn Key);
+            }
+        }
+
+        item?.ok_or(Error::KeyNotFoundOrLoadable)
+    }
+
+    /// Returns the values of the given keys and marks them to be kept alive.
+    ///
+    /// Missing keys are loaded in a single call to the batch loader of type K, if any, or else one by one via the value method.
+    /// Keys which could not be found nor loaded are left out.
+    pub fn get_many<K: 'static + TypedMap + Send + Sync + Clone>(
+        &self,
+        keys: impl IntoIterator<Item = K>,
+    ) -> HashMap<K, TypedRef<K::Value>>
+    where
+        K::Value: Send + Sync,
+    {
+        let (mut found, missing) = self.lookup_many(keys);
+        if missing.is_empty() {
+            return found;
+        }
+        if let Some(batch_loader) = self.batch_loader::<K>() {
+            self.inner
+                .stats
+                .record_misses(KeyType::of::<K>(), missing.len());
+            self.load_batch(&batch_loader, missing.clone());
+            found.extend(self.get_values(missing));
+        } else {
+            for key in missing {
+                if let Some(value) = self
+                    .value(key.clone())
+                    .ok()
+                    .and_then(|item| item.value().typed_ref())
+                {
+                    found.insert(key, value);
+                }
+            }
+        }
+        found
+    }
+
+    /// Returns the values of the given keys and marks them to be kept alive, loading missing keys asynchronously.
+    ///
+    /// Unlike get_many, misses from concurrent calls are collected into a single batch during the window of the batch loader.
+    /// Without a batch loader, missing keys are loaded one by one via the value_async method.
+    pub async fn get_many_async<K: 'static + TypedMap + Send + Sync + Clone>(
+        &self,
+        keys: impl IntoIterator<Item = K>,
+    ) -> HashMap<K, TypedRef<K::Value>>
+    where
+        K::Value: Send + Sync,
+    {
+        let (mut found, missing) = self.lookup_many(keys);
+        if missing.is_empty() {
+            return found;
+        }
+        let Some(batch_loader) = self.batch_loader::<K>() else {
+            for key in missing {
+                if let Some(value) = self
+                    .value_async(key.clone())
+                    .await
+                    .ok()
+                    .and_then(|item| item.value().typed_ref())
+                {
+                    found.insert(key, value);
+                }
+            }
+            return found;
+        };
+        self.inner
+            .stats
+            .record_misses(KeyType::of::<K>(), missing.len());
+        if batch_loader.window == Duration::ZERO {
+            self.load_batch(&batch_loader, missing.clone());
+        } else {
+            let mut done = {
+                let mut pending = batch_loader.pending.lock().unwrap();
+                let batch = match pending.as_ref() {
+                    Some(batch) => batch.clone(),
+                    None => {
+                        let batch = Arc::new(Batch::new());
+                        *pending = Some(batch.clone());
+                        tokio::spawn({
+                            let cache_table = self.clone();
+                            let batch_loader = batch_loader.clone();
+                            let batch = batch.clone();
+                            async move {
+                                tokio::time::sleep(batch_loader.window).await;
+                                batch_loader.pending.lock().unwrap().take();
+                                let keys = std::mem::take(&mut *batch.keys.lock().unwrap());
+                                cache_table.load_batch(&batch_loader, keys);
+                                batch.done.send_replace(true);
+                            }
+                        });
+                        batch
+                    }
+                };
+                batch.keys.lock().unwrap().extend(missing.iter().cloned());
+                batch.done.subscribe()
+            };
+            // The sender only goes away once the batch is done.
+            _ = done.wait_for(|done| *done).await;
+        }
+        found.extend(self.get_values(missing));
+        found
+    }
+
+    /// Looks up cached values, marking them to be kept alive, and returns them along with the missing keys.
+    fn lookup_many<K: 'static + TypedMap + Send + Sync + Clone>(
+        &self,
+        keys: impl IntoIterator<Item = K>,
+    ) -> (HashMap<K, TypedRef<K::Value>>, Vec<K>)
+    where
+        K::Value: Send + Sync,
+    {
+        let mut found = HashMap::new();
+        let mut missing = Vec::new();
+        for key in keys {
+            match self.peek(&key) {
+                Some(item) => {
+                    self.hit(&key, &item);
+                    if let Some(value) = item.value().typed_ref() {
+                        found.insert(key, value);
+                    }
+                }
+                None => missing.push(key),
+            }
+        }
+        (found, missing)
+    }
+
+    fn get_values<K: 'static + TypedMap + Send + Sync + Clone>(
+        &self,
+        keys: Vec<K>,
+    ) -> impl Iterator<Item = (K, TypedRef<K::Value>)> + '_
+    where
+        K::Value: Send + Sync,
+    {
+        keys.into_iter().filter_map(|key| {
+            let value = self.peek(&key)?.value().typed_ref()?;
+            Some((key, value))
+        })
+    }
+
+    /// Loads the given keys with a single call to the batch loader and adds them to the cache.
+    fn load_batch<K: 'static + TypedMap + Send + Sync + Clone>(
+        &self,
+        batch_loader: &BatchLoader<K>,
+        keys: Vec<K>,
+    ) where
+        K::Value: Send + Sync,
+    {
+        let keys: Vec<K> = keys
+            .into_iter()
+            .collect::<HashSet<_>>()
+            .into_iter()
+            .collect();
+        if keys.is_empty() {
+            return;
+        }
+        tracing::trace!(
+            "Loading batch of {} keys into table {}",
+            keys.len(),
+            self.inner.name
+        );
+        let start = Instant::now();
+        let values = (batch_loader.load)(keys);
+        self.loaded(start, &Ok::<_, BoxError>(()));
+        for (key, value) in values {
+            let item = CacheItem::new(key.clone(), batch_loader.life_span, value);
+            self.add_internal(key, item);
+        }
+    }
+
     /// Deletes all items from this cache table.
     pub fn flush(&self) {
         tracing::trace!("Flushing table {}", self.inner.name);
-        self.inner.items.write().unwrap().clear();
-        self.inner.clean_up_interval.store(Arc::new(Duration::ZERO));
+        let items = std::mem::replace(
+            &mut *self.inner.items.write().unwrap(),
+            Items::new(self.inner.eviction_policy.clone()),
+        );
+        self.inner.next_clean_up.store(Arc::new(None));
+        self.inner.misses.lock().unwrap().clear();
+        let mut notifications = Notifications::default();
+        notifications.flushed(items.values().cloned().collect());
+        self.dispatch(notifications);
+    }
+}
+
+/// Items holds the cached items along with the bookkeeping of their weights, usage by key type, deadlines and eviction order.
+pub(crate) struct Items {
+    map: HashMap<TypedKey, CacheItem>,
+    /// The total weight of all items.
+    weight: usize,
+    /// The number and total weight of items, by key type.
+    usage: HashMap<TypeId, Usage>,
+    /// The number of stale items, which are only kept for the grace period, by key type.
+    stale: HashMap<TypeId, usize>,
+    /// The deadlines of expiring items, the earliest first.
+    ///
+    /// Deadlines are not updated when an item is accessed or removed. Instead, stale deadlines are
+    /// skipped or rescheduled once they are due.
+    deadlines: BinaryHeap<Reverse<Deadline>>,
+    /// Ranks the items for eviction.
+    policy: Arc<dyn EvictionPolicy>,
+    /// Whether the policy ranks items, otherwise victims are selected among all items.
+    ranked: bool,
+    /// The candidates for eviction by key type, the lowest rank first.
+    ///
+    /// Like deadlines, candidates are not updated when an item is accessed or removed. Instead,
+    /// they are skipped or ranked again once they come up.
+    candidates: HashMap<TypeId, BinaryHeap<Reverse<Candidate>>>,
+}
+
+impl Items {
+    pub(crate) fn new(policy: Arc<dyn EvictionPolicy>) -> Self {
+        Self {
+            map: HashMap::new(),
+            weight: 0,
+            usage: HashMap::new(),
+            stale: HashMap::new(),
+            deadlines: BinaryHeap::new(),
+            policy,
+            ranked: true,
+            candidates: HashMap::new(),
+        }
+    }
+
+    pub(crate) fn get(&self, key: &dyn Key) -> Option<&CacheItem> {
+        self.map.get(key)
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (&TypedKey, &CacheItem)> {
+        self.map.iter()
+    }
+
+    pub(crate) fn values(&self) -> impl Iterator<Item = &CacheItem> {
+        self.map.values()
+    }
+
+    pub(crate) fn insert(&mut self, key: TypedKey, item: CacheItem) -> Option<CacheItem> {
+        self.account(&item, true);
+        self.schedule(&item);
+        self.enqueue(&item);
+        let ret = self.map.insert(key, item);
+        if let Some(old) = &ret {
+            self.account(old, false);
+            self.compact_if_needed();
+        }
+        ret
+    }
+
+    pub(crate) fn remove(&mut self, key: &dyn Key) -> Option<CacheItem> {
+        let ret = self.map.remove(key);
+        if let Some(old) = &ret {
+            self.account(old, false);
+            self.compact_if_needed();
+        }
+        ret
+    }
+
+    /// Removes and returns the items whose deadline has passed.
+    ///
+    /// Items expiring for the first time are kept as stale for the given grace period instead.
+    fn expire(&mut self, now: Instant, grace_period: Duration) -> Vec<CacheItem> {
+        let mut expired = Vec::new();
+        while self.deadlines.peek().is_some_and(|Reverse(d)| d.at <= now) {
+            let Some(Reverse(deadline)) = self.deadlines.pop() else {
+                break;
+            };
+            let Some(item) = deadline.item.upgrade().map(|inner| CacheItem { inner }) else {
+                continue;
+            };
+            if !self
+                .map
+                .get(item.key() as &dyn Key)
+                .is_some_and(|i| i.ptr_eq(&item))
+            {
+                continue;
+            }
+            match item.expires_at() {
+                Some(at) if at <= now => {
+                    if !item.is_stale() && at + grace_period > now {
+                        item.set_stale_until(at + grace_period);
+                        *self.stale.entry(item.key().key_type_id()).or_default() += 1;
+                        self.schedule(&item);
+                        self.enqueue(&item);
+                    } else if let Some(item) = self.remove(item.key() as &dyn Key) {
+                        expired.push(item);
+                    }
+                }
+                Some(at) => self.deadlines.push(Reverse(Deadline {
+                    at,
+                    item: deadline.item,
+                })),
+                None => {}
+            }
+        }
+        expired
+    }
+
+    /// Returns the earliest scheduled deadline.
+    fn next_deadline(&self) -> Option<Instant> {
+        self.deadlines.peek().map(|Reverse(d)| d.at)
+    }
+
+    /// Schedules the item again if its deadline moved before the previous one.
+    fn reschedule(&mut self, item: &CacheItem, previous: Option<Instant>) {
+        if item
+            .expires_at()
+            .is_some_and(|at| previous.is_none_or(|previous| at < previous))
+        {
+            self.schedule(item);
+        }
+    }
+
+    fn schedule(&mut self, item: &CacheItem) {
+        if let Some(at) = item.expires_at() {
+            self.deadlines.push(Reverse(Deadline {
+                at,
+                item: Arc::downgrade(&item.inner),
+            }));
+        }
+    }
+
+    /// Compacts the deadlines and eviction candidates once outdated ones outnumber the cached items.
+    fn compact_if_needed(&mut self) {
+        if self.deadlines.len() > 2 * self.map.len() + 64 {
+            self.compact();
+        }
+        if self.candidates.values().map(BinaryHeap::len).sum::<usize>() > 2 * self.map.len() + 64 {
+            self.candidates.clear();
+            for item in self.map.values().cloned().collect::<Vec<_>>() {
+                self.enqueue(&item);
+            }
+        }
+    }
+
+    /// Returns the key an item is evicted by, stale items coming first.
+    fn eviction_key(&self, item: &CacheItem) -> Option<(bool, Rank)> {
+        let rank = self.policy.rank(item)?;
+        Some((!item.is_stale(), rank))
+    }
+
+    /// Queues the item for eviction at its current rank.
+    fn enqueue(&mut self, item: &CacheItem) {
+        let Some(key) = self.eviction_key(item) else {
+            self.ranked = false;
+            return;
+        };
+        self.candidates
+            .entry(item.key().key_type_id())
+            .or_default()
+            .push(Reverse(Candidate {
+                key,
+                item: Arc::downgrade(&item.inner),
+            }));
+    }
+
+    /// Returns the next item to evict with the given key type or any, except the spared one, without removing it.
+    ///
+    /// It may only be called if the policy ranks items.
+    fn next_victim(
+        &mut self,
+        spare: Option<&CacheItem>,
+        type_id: Option<TypeId>,
+    ) -> Option<CacheItem> {
+        let mut spared = Vec::new();
+        let victim = loop {
+            let type_id = match type_id {
+                Some(type_id) => type_id,
+                None => match self
+                    .candidates
+                    .iter()
+                    .filter_map(|(type_id, heap)| heap.peek().map(|Reverse(c)| (c.key, *type_id)))
+                    .min()
+                {
+                    Some((_, type_id)) => type_id,
+                    None => break None,
+                },
+            };
+            let Some(Reverse(candidate)) =
+                self.candidates.get_mut(&type_id).and_then(BinaryHeap::pop)
+            else {
+                break None;
+            };
+            let Some(item) = candidate.item.upgrade().map(|inner| CacheItem { inner }) else {
+                continue;
+            };
+            if !self
+                .map
+                .get(item.key() as &dyn Key)
+                .is_some_and(|i| i.ptr_eq(&item))
+            {
+                continue;
+            }
+            if spare.is_some_and(|spare| spare.ptr_eq(&item)) {
+                spared.push(candidate);
+                continue;
+            }
+            let Some(key) = self.eviction_key(&item) else {
+                continue;
+            };
+            // The item was used since it was queued, so it is queued again at its current rank.
+            let current = key == candidate.key;
+            self.candidates
+                .entry(type_id)
+                .or_default()
+                .push(Reverse(Candidate {
+                    key,
+                    item: candidate.item,
+                }));
+            if current {
+                break Some(item);
+            }
+        };
+        for candidate in spared {
+            if let Some(item) = candidate.item.upgrade() {
+                let type_id = CacheItem { inner: item }.key().key_type_id();
+                self.candidates
+                    .entry(type_id)
+                    .or_default()
+                    .push(Reverse(candidate));
+            }
+        }
+        victim
+    }
+
+    /// Drops the deadlines of items which are no longer cached, keeping only the earliest deadline of each item.
+    fn compact(&mut self) {
+        let mut seen = HashSet::new();
+        let deadlines = std::mem::take(&mut self.deadlines).into_sorted_vec();
+        self.deadlines = deadlines
+            .into_iter()
+            .rev()
+            .filter(|Reverse(d)| {
+                d.item.upgrade().is_some_and(|inner| {
+                    let item = CacheItem { inner };
+                    self.map
+                        .get(item.key() as &dyn Key)
+                        .is_some_and(|i| i.ptr_eq(&item))
+                        && seen.insert(Arc::as_ptr(&item.inner))
+                })
+            })
+            .collect();
+    }
+
+    /// Changes the weight of a cached item.
+    fn reweigh(&mut self, item: &CacheItem, weight: usize) {
+        self.account(item, false);
+        item.set_weight(weight);
+        self.account(item, true);
+    }
+
+    fn account(&mut self, item: &CacheItem, added: bool) {
+        let type_id = item.key().key_type_id();
+        let usage = self.usage.entry(type_id).or_default();
+        if added {
+            self.weight += item.weight();
+            if item.is_stale() {
+                *self.stale.entry(type_id).or_default() += 1;
+            }
+            usage.count += 1;
+            usage.weight += item.weight();
+        } else {
+            self.weight -= item.weight();
+            if item.is_stale() {
+                if let Some(stale) = self.stale.get_mut(&type_id) {
+                    *stale -= 1;
+                    if *stale == 0 {
+                        self.stale.remove(&type_id);
+                    }
+                }
+            }
+            usage.count -= 1;
+            usage.weight -= item.weight();
+            if usage.count == 0 {
+                self.usage.remove(&type_id);
+            }
+        }
+    }
+}
+
+/// Notification is a change of the items whose callbacks are yet to be triggered.
+pub(crate) enum Notification {
+    Added(CacheItem),
+    Replaced(CacheItem, CacheItem),
+    Removed(CacheItem, RemovalCause),
+    Flushed(Vec<CacheItem>),
+}
+
+/// Notifications queues the changes made while the items are locked, to be dispatched once they are unlocked.
+#[derive(Default)]
+#[must_use]
+pub(crate) struct Notifications(Vec<Notification>);
+
+impl Notifications {
+    pub(crate) fn added(&mut self, item: CacheItem) {
+        self.0.push(Notification::Added(item));
+    }
+
+    pub(crate) fn replaced(&mut self, old: CacheItem, new: CacheItem) {
+        self.0.push(Notification::Replaced(old, new));
+    }
+
+    pub(crate) fn removed(&mut self, item: CacheItem, cause: RemovalCause) {
+        self.0.push(Notification::Removed(item, cause));
+    }
+
+    pub(crate) fn flushed(&mut self, items: Vec<CacheItem>) {
+        self.0.push(Notification::Flushed(items));
+    }
+}
+
+/// Returns the time left until the given deadline.
+fn remaining(deadline: Option<Instant>, now: Instant) -> Option<Duration> {
+    deadline.map(|deadline| deadline.saturating_duration_since(now))
+}
+
+/// Candidate is an item queued for eviction, along with its key at the time.
+struct Candidate {
+    key: (bool, Rank),
+    item: Weak<CacheItemInner>,
+}
+
+impl PartialEq for Candidate {
+    fn eq(&self, other: &Self) -> bool {
+        self.key == other.key
+    }
+}
+
+impl Eq for Candidate {}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.key.cmp(&other.key)
+    }
+}
+
+/// Deadline is the point in time an item is scheduled to be checked for expiration.
+struct Deadline {
+    at: Instant,
+    item: Weak<CacheItemInner>,
+}
+
+impl PartialEq for Deadline {
+    fn eq(&self, other: &Self) -> bool {
+        self.at == other.at
+    }
+}
+
+impl Eq for Deadline {}
+
+impl PartialOrd for Deadline {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Deadline {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.at.cmp(&other.at)
     }
 }
@@ -5,6 +5,7 @@ pub trait DynEq: Any {
     fn dyn_eq(&self, other: &dyn DynEq) -> bool;
     fn as_any(&self) -> &dyn Any;
     fn as_any_box(self: Box<Self>) -> Box<dyn Any>;
+    fn type_name(&self) -> &'static str;
 }
 
 pub trait DynHash: DynEq {
@@ -27,6 +28,10 @@ impl<H: Eq + Any> DynEq for H {
     fn as_any_box(self: Box<Self>) -> Box<dyn Any> {
         self
     }
+
+    fn type_name(&self) -> &'static str {
+        std::any::type_name::<H>()
+    }
 }
 
 impl<H: Hash + DynEq> DynHash for H {
@@ -54,6 +59,17 @@ impl DowncastDynHash for Box<dyn DynHash> {
     }
 }
 
+impl DowncastDynHash for Box<dyn DynHash + Send + Sync> {
+    fn downcast<T: Any>(self) -> Result<Box<T>, Self> {
+        if (*self).as_any().is::<T>() {
+            let downcasted = self.as_any_box().downcast().expect("Broken Any downcast");
+            Ok(downcasted)
+        } else {
+            Err(self)
+        }
+    }
+}
+
 impl PartialEq for dyn DynHash {
     fn eq(&self, other: &dyn DynHash) -> bool {
         self.dyn_eq(other.as_dyn_eq())
@@ -1,4 +1,4 @@
-use std::any::Any;
+use std::any::{Any, TypeId};
 use std::hash::{Hash, Hasher};
 use std::marker::PhantomData;
 
@@ -79,6 +79,16 @@ where
     pub fn as_any(&self) -> &dyn Any {
         self.key.as_ref().as_any()
     }
+
+    /// Returns the TypeId of the underlying key type.
+    pub fn key_type_id(&self) -> TypeId {
+        self.as_any().type_id()
+    }
+
+    /// Returns the name of the underlying key type.
+    pub fn key_type_name(&self) -> &'static str {
+        self.key.as_ref().type_name()
+    }
 }
 
 pub trait Key {
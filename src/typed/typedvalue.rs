@@ -1,27 +1,107 @@
 use std::any::Any;
+use std::fmt;
+use std::ops::Deref;
+use std::sync::{Arc, RwLock};
 
 pub type TypedValue = TypedValueBase<dyn Any + 'static + Send + Sync>;
 
-pub struct TypedValueBase<T: ?Sized + 'static + Any = dyn Any + 'static>(Box<T>);
+pub struct TypedValueBase<T: ?Sized + 'static + Any = dyn Any + 'static>(RwLock<Arc<T>>);
 
 impl TypedValueBase<dyn Any + Send + Sync + 'static> {
     pub fn from_value<V: Any + Send + Sync + 'static>(value: V) -> Self {
-        Self(Box::new(value))
+        Self(RwLock::new(Arc::new(value)))
     }
-    
+
+    /// Takes the value out if it is of type `V`.
+    ///
+    /// Returns None as well if the value is still shared, e.g. through a [`TypedRef`] snapshot.
     #[must_use]
-    pub fn downcast<V: Any>(self) -> Option<V> {
-        let boxed: Box<dyn Any + 'static> = self.0;
-        let boxed: Option<Box<V>> = boxed.downcast().ok();
-        boxed.map(|v| *v)
+    pub fn downcast<V: Any + Send + Sync>(self) -> Option<V> {
+        let arc: Arc<V> = self.0.into_inner().unwrap().downcast().ok()?;
+        Arc::try_unwrap(arc).ok()
     }
 
+    /// Returns a typed read-only handle to the current value if it is of type `V`.
+    ///
+    /// The handle is a snapshot: later updates of the value are not visible through it.
+    #[deprecated(note = "use typed_ref, which returns the same snapshot")]
     #[must_use]
-    pub fn downcast_ref<V: Any>(&self) -> Option<&V> {
-        self.0.as_ref().downcast_ref::<V>()
+    pub fn downcast_ref<V: Any + Send + Sync>(&self) -> Option<TypedRef<V>> {
+        self.typed_ref()
     }
 
+    /// Returns a mutable reference to the value if it is of type `V`.
+    ///
+    /// Returns None as well if the value is still shared, e.g. through a [`TypedRef`] snapshot.
     pub fn downcast_mut<V: Any>(&mut self) -> Option<&mut V> {
-        self.0.as_mut().downcast_mut::<V>()
+        Arc::get_mut(self.0.get_mut().unwrap())?.downcast_mut::<V>()
+    }
+
+    /// Returns a typed read-only handle to the current value if it is of type `V`.
+    ///
+    /// The handle is a snapshot: later updates of the value are not visible through it.
+    #[must_use]
+    pub fn typed_ref<V: Any + Send + Sync>(&self) -> Option<TypedRef<V>> {
+        self.downcast_arc().map(TypedRef)
+    }
+
+    /// Returns a shared handle to the current value if it is of type `V`.
+    #[must_use]
+    pub fn downcast_arc<V: Any + Send + Sync>(&self) -> Option<Arc<V>> {
+        self.0.read().unwrap().clone().downcast().ok()
+    }
+
+    /// Mutates the value in place if it is of type `V`.
+    ///
+    /// If snapshots of the value are still held elsewhere, e.g. from typed_ref, the whole value is
+    /// cloned first so that they stay unchanged. Drop snapshots before updating large values, such as
+    /// a cached Vec being appended to, to avoid the copy.
+    pub fn update<V: Any + Send + Sync + Clone, R>(
+        &self,
+        f: impl FnOnce(&mut V) -> R,
+    ) -> Option<R> {
+        let mut value = self.0.write().unwrap();
+        if !value.is::<V>() {
+            return None;
+        }
+        if Arc::get_mut(&mut value).is_none() {
+            let copy = value.downcast_ref::<V>()?.clone();
+            *value = Arc::new(copy);
+        }
+        Arc::get_mut(&mut value)?.downcast_mut::<V>().map(f)
+    }
+}
+
+/// TypedRef is a typed read-only handle to a cached value.
+///
+/// It keeps the value alive independently of the cache, so it stays valid after
+/// the item has been removed from its table.
+pub struct TypedRef<V>(Arc<V>);
+
+impl<V> TypedRef<V> {
+    /// Converts this handle into the shared value.
+    #[must_use]
+    pub fn into_arc(self) -> Arc<V> {
+        self.0
+    }
+}
+
+impl<V> Clone for TypedRef<V> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<V> Deref for TypedRef<V> {
+    type Target = V;
+
+    fn deref(&self) -> &V {
+        &self.0
+    }
+}
+
+impl<V: fmt::Debug> fmt::Debug for TypedRef<V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
     }
 }
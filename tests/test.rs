@@ -1,6 +1,26 @@
-use std::time::Duration;
+use std::{
+    any::TypeId,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
 
-use typedcache::typed::TypedMap;
+use typedcache::{
+    clock::{Clock, MockClock},
+    entry::Entry,
+    error::Error,
+    event::CacheEvent,
+    eviction::{Fifo, Lfu, Lru},
+    expiry::Expiry,
+    item::{CacheItem, RemovalCause},
+    quota::{Quota, Usage},
+    stale::StaleMode,
+    stats::{Stats, TypeStats},
+    table::CacheTable,
+    typed::{typedkey::TypedKey, TypedMap},
+};
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
 pub struct TestKey(usize);
@@ -8,6 +28,7 @@ pub struct TestKey(usize);
 impl TypedMap for TestKey {
     type Value = TestValue;
 }
+#[derive(Clone)]
 pub struct TestValue(usize);
 
 #[tokio::test]
@@ -16,3 +37,1069 @@ async fn not_found_add() {
     assert!(cache.not_found_add(TestKey(1), Duration::ZERO, TestValue(1)));
     assert!(!cache.not_found_add(TestKey(1), Duration::ZERO, TestValue(1)));
 }
+
+#[tokio::test]
+async fn get_value() {
+    let cache = typedcache::cache("get_value".into());
+    cache.add(TestKey(1), Duration::ZERO, TestValue(1));
+    assert_eq!(cache.get_value(&TestKey(1)).map(|v| v.0), Some(1));
+    assert_eq!(cache.get_value_arc(&TestKey(1)).map(|v| v.0), Some(1));
+    assert!(cache.get_value(&TestKey(2)).is_none());
+}
+
+#[tokio::test]
+async fn entry() {
+    let mut cache = typedcache::cache("entry".into());
+    let deleted = Arc::new(AtomicUsize::new(0));
+    cache.add_about_to_delete_item_callback({
+        let deleted = deleted.clone();
+        move |_, _| {
+            deleted.fetch_add(1, Ordering::Relaxed);
+        }
+    });
+    cache.set_weigher(|_: &TestKey, value: &TestValue| value.0);
+
+    let item = cache
+        .entry(TestKey(1))
+        .and_modify(|_| unreachable!())
+        .or_insert_with(Duration::ZERO, || TestValue(1));
+    assert_eq!(item.value().typed_ref::<TestValue>().map(|v| v.0), Some(1));
+
+    let item = cache
+        .entry(TestKey(1))
+        .and_modify(|value| value.0 += 1)
+        .or_insert(Duration::ZERO, TestValue(5));
+    assert_eq!(item.value().typed_ref::<TestValue>().map(|v| v.0), Some(2));
+    assert_eq!(cache.usage::<TestKey>().weight, 2);
+
+    match cache.entry(TestKey(1)) {
+        Entry::Occupied(entry) => {
+            entry.remove();
+        }
+        Entry::Vacant(_) => unreachable!(),
+    }
+    assert!(cache.get(&TestKey(1)).is_none());
+    assert_eq!(deleted.load(Ordering::Relaxed), 1);
+}
+
+#[tokio::test]
+async fn update() {
+    let cache = typedcache::cache("update".into());
+    let item = cache.add(TestKey(1), Duration::ZERO, TestValue(1));
+    assert!(item.is_none());
+    let snapshot = cache.get_value(&TestKey(1)).unwrap();
+    let item = cache.get(&TestKey(1)).unwrap();
+    item.keep_alive();
+    let typed_ref = item.value().typed_ref::<TestValue>().unwrap();
+
+    assert_eq!(
+        cache
+            .update(&TestKey(1), |v| {
+                v.0 += 1;
+                v.0
+            })
+            .unwrap(),
+        2
+    );
+    assert_eq!(snapshot.0, 1);
+    assert_eq!(typed_ref.0, 1);
+    assert_eq!(item.value().typed_ref::<TestValue>().map(|v| v.0), Some(2));
+    assert_eq!(cache.get_value(&TestKey(1)).map(|v| v.0), Some(2));
+    assert_eq!(
+        cache.get(&TestKey(1)).map(|item| item.access_count()),
+        Some(1)
+    );
+
+    let res: Result<(), Error> = cache.try_update(&TestKey(2), |_| Ok(()));
+    assert!(matches!(res, Err(Error::KeyNotFound)));
+}
+
+#[tokio::test]
+async fn eviction() {
+    let mut lru = CacheTable::with_capacity("eviction_lru".into(), 2, Lru);
+    let evicted = Arc::new(AtomicUsize::new(0));
+    lru.add_about_to_delete_item_callback({
+        let evicted = evicted.clone();
+        move |item, cause| {
+            assert_eq!(cause, RemovalCause::Evicted);
+            evicted.store(
+                item.key().downcast_ref::<TestKey>().unwrap().0,
+                Ordering::Relaxed,
+            );
+        }
+    });
+    lru.add(TestKey(1), Duration::ZERO, TestValue(1));
+    lru.add(TestKey(2), Duration::ZERO, TestValue(2));
+    assert!(lru.value(TestKey(1)).is_ok());
+    lru.add(TestKey(3), Duration::ZERO, TestValue(3));
+    assert_eq!(lru.count(), 2);
+    assert!(lru.get(&TestKey(2)).is_none());
+    assert_eq!(evicted.load(Ordering::Relaxed), 2);
+
+    let lfu = CacheTable::with_capacity("eviction_lfu".into(), 2, Lfu);
+    lfu.add(TestKey(1), Duration::ZERO, TestValue(1));
+    lfu.add(TestKey(2), Duration::ZERO, TestValue(2));
+    assert!(lfu.value(TestKey(1)).is_ok());
+    assert!(lfu.value(TestKey(1)).is_ok());
+    assert!(lfu.value(TestKey(2)).is_ok());
+    lfu.add(TestKey(3), Duration::ZERO, TestValue(3));
+    assert!(lfu.get(&TestKey(2)).is_none());
+    assert!(lfu.get(&TestKey(3)).is_some());
+
+    let fifo = CacheTable::with_capacity("eviction_fifo".into(), 2, Fifo);
+    fifo.add(TestKey(1), Duration::ZERO, TestValue(1));
+    fifo.add(TestKey(2), Duration::ZERO, TestValue(2));
+    assert!(fifo.value(TestKey(1)).is_ok());
+    assert!(fifo.not_found_add(TestKey(3), Duration::ZERO, TestValue(3)));
+    assert!(fifo.get(&TestKey(1)).is_none());
+    assert_eq!(fifo.count(), 2);
+
+    let mut registered = typedcache::cache_with_capacity("eviction_registered".into(), 2, Fifo);
+    assert_eq!(
+        typedcache::cache("eviction_registered".into()).capacity(),
+        2
+    );
+    let clock = MockClock::new();
+    registered.set_clock(clock.clone());
+    for i in 0..3 {
+        registered.add(TestKey(i), Duration::ZERO, TestValue(i));
+        clock.advance(Duration::from_secs(1));
+    }
+    assert_eq!(registered.count(), 2);
+    registered.set_capacity(1);
+    assert_eq!(registered.count(), 1);
+    assert!(registered.exists(TestKey(2)));
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct BlobKey(usize);
+
+impl TypedMap for BlobKey {
+    type Value = Vec<u8>;
+}
+
+#[tokio::test]
+async fn weight() {
+    let mut cache = CacheTable::with_capacity("weight".into(), 0, Fifo);
+    cache.set_max_weight(10);
+    cache.set_weigher::<BlobKey>(|_, v| v.len());
+    cache.add(BlobKey(1), Duration::ZERO, vec![0; 4]);
+    cache.add(BlobKey(2), Duration::ZERO, vec![0; 4]);
+    cache.add(TestKey(1), Duration::ZERO, TestValue(1));
+    assert_eq!(cache.weight(), 9);
+
+    cache.add(BlobKey(2), Duration::ZERO, vec![0; 2]);
+    assert_eq!(cache.weight(), 7);
+
+    cache.update(&BlobKey(2), |v| v.extend([0; 4])).unwrap();
+    assert_eq!(cache.weight(), 7);
+    assert!(cache.get(&BlobKey(1)).is_none());
+
+    assert!(cache.delete(&TestKey(1)).is_ok());
+    assert_eq!(cache.weight(), 6);
+
+    cache.set_max_weight(5);
+    assert_eq!(cache.count(), 0);
+    assert_eq!(cache.weight(), 0);
+
+    cache.add(BlobKey(3), Duration::ZERO, vec![0; 2]);
+    cache.add(BlobKey(4), Duration::ZERO, vec![0; 50]);
+    assert_eq!(cache.count(), 0);
+    assert_eq!(cache.weight(), 0);
+    assert_eq!(cache.stats().evictions, 4);
+}
+
+#[tokio::test]
+async fn quota() {
+    let mut cache = CacheTable::with_capacity("quota".into(), 0, Fifo);
+    cache.set_weigher::<BlobKey>(|_, v| v.len());
+    cache.set_quota::<BlobKey>(Quota {
+        max_count: 3,
+        max_weight: 8,
+    });
+    cache.add(TestKey(1), Duration::ZERO, TestValue(1));
+    cache.add(BlobKey(1), Duration::ZERO, vec![0; 4]);
+    cache.add(BlobKey(2), Duration::ZERO, vec![0; 4]);
+    assert!(cache.not_found_add(BlobKey(3), Duration::ZERO, vec![0; 1]));
+    assert!(cache.get(&BlobKey(1)).is_none());
+    assert!(cache.get(&TestKey(1)).is_some());
+    assert_eq!(
+        cache.usage::<BlobKey>(),
+        Usage {
+            count: 2,
+            weight: 5
+        }
+    );
+    assert_eq!(cache.usage::<TestKey>().count, 1);
+    assert_eq!(cache.usage_by_type().len(), 2);
+
+    cache.set_quota::<BlobKey>(Quota {
+        max_count: 1,
+        max_weight: 0,
+    });
+    assert_eq!(cache.usage::<BlobKey>().count, 1);
+    assert!(cache.get(&BlobKey(3)).is_some());
+
+    cache.set_quota::<BlobKey>(Quota {
+        max_count: 0,
+        max_weight: 8,
+    });
+    cache.add(BlobKey(4), Duration::ZERO, vec![0; 20]);
+    assert_eq!(cache.usage::<BlobKey>(), Usage::default());
+    assert!(cache.get(&TestKey(1)).is_some());
+}
+
+#[tokio::test]
+async fn clean_up() {
+    let clock = MockClock::new();
+    let mut cache = CacheTable::new("clean_up".into());
+    cache.set_clock(clock.clone());
+    cache.add(TestKey(1), Duration::from_secs(20), TestValue(1));
+    cache.add(TestKey(2), Duration::from_secs(20), TestValue(2));
+    cache.add(TestKey(3), Duration::ZERO, TestValue(3));
+    clock.advance(Duration::from_secs(10));
+    assert!(cache.value(TestKey(2)).is_ok());
+    clock.advance(Duration::from_secs(15));
+    assert_eq!(cache.clean_up(), Some(clock.now() + Duration::from_secs(5)));
+    assert!(cache.get(&TestKey(1)).is_none());
+    assert!(cache.get(&TestKey(2)).is_some());
+    clock.advance(Duration::from_secs(5));
+    assert!(cache.clean_up().is_none());
+    assert!(cache.get(&TestKey(2)).is_none());
+    assert!(cache.get(&TestKey(3)).is_some());
+}
+
+#[tokio::test]
+async fn replace_deadlines() {
+    let clock = MockClock::new();
+    let mut cache = CacheTable::new("replace_deadlines".into());
+    cache.set_clock(clock.clone());
+    cache.add(TestKey(1), Duration::from_secs(1), TestValue(0));
+    for i in 1..100 {
+        cache.add(TestKey(1), Duration::from_secs(60), TestValue(i));
+    }
+    // The deadline of the replaced item is dropped rather than kept until it is due.
+    assert_eq!(
+        cache.clean_up(),
+        Some(clock.now() + Duration::from_secs(60))
+    );
+}
+
+#[tokio::test]
+async fn time_to_live() {
+    let clock = MockClock::new();
+    let mut cache = CacheTable::new("time_to_live".into());
+    cache.set_clock(clock.clone());
+    cache.add_with_time_to_live(
+        TestKey(1),
+        Duration::from_secs(60),
+        Duration::from_secs(20),
+        TestValue(1),
+    );
+    let item = cache.value(TestKey(1)).unwrap();
+    assert_eq!(item.created_on(), clock.now());
+    assert_eq!(
+        item.expires_at(),
+        Some(item.created_on() + Duration::from_secs(20))
+    );
+    clock.advance(Duration::from_secs(10));
+    assert!(cache.value(TestKey(1)).is_ok());
+    assert_eq!(item.accessed_on(), clock.now());
+    clock.advance(Duration::from_secs(9));
+    cache.clean_up();
+    assert!(cache.get(&TestKey(1)).is_some());
+    clock.advance(Duration::from_secs(1));
+    cache.clean_up();
+    assert!(cache.get(&TestKey(1)).is_none());
+}
+
+struct MaxAge;
+
+impl Expiry<BlobKey> for MaxAge {
+    fn expire_after_create(&self, _: &BlobKey, value: &Vec<u8>, _: Instant) -> Option<Duration> {
+        Some(Duration::from_millis(value.len() as u64))
+    }
+
+    fn expire_after_update(
+        &self,
+        _: &BlobKey,
+        value: &Vec<u8>,
+        _: Instant,
+        _: Option<Duration>,
+    ) -> Option<Duration> {
+        Some(Duration::from_millis(value.len() as u64))
+    }
+}
+
+#[tokio::test]
+async fn expiry() {
+    let clock = MockClock::new();
+    let mut cache = CacheTable::new("expiry".into());
+    cache.set_clock(clock.clone());
+    cache.set_expiry(MaxAge);
+    let item = cache.add(BlobKey(1), Duration::ZERO, vec![0; 1000]);
+    assert!(item.is_none());
+    cache.add(BlobKey(2), Duration::ZERO, vec![0; 1000]);
+    let item = cache.value(BlobKey(1)).unwrap();
+    assert_eq!(
+        item.expires_at(),
+        Some(clock.now() + Duration::from_secs(1))
+    );
+    cache.update(&BlobKey(2), |v| v.truncate(10)).unwrap();
+    clock.advance(Duration::from_millis(10));
+    cache.clean_up();
+    assert!(cache.get(&BlobKey(1)).is_some());
+    assert!(cache.get(&BlobKey(2)).is_none());
+}
+
+#[tokio::test]
+async fn value_async() {
+    let mut cache = CacheTable::new("value_async".into());
+    let loads = Arc::new(AtomicUsize::new(0));
+    cache.set_async_data_loader({
+        let loads = loads.clone();
+        move |key: TypedKey| {
+            let loads = loads.clone();
+            Box::pin(async move {
+                loads.fetch_add(1, Ordering::Relaxed);
+                tokio::time::sleep(Duration::from_millis(10)).await;
+                let key = key.downcast::<TestKey>().ok()?;
+                let value = TestValue(key.0);
+                Some(CacheItem::new(key, Duration::ZERO, value))
+            })
+        }
+    });
+
+    let tasks: Vec<_> = (0..10)
+        .map(|_| {
+            let cache = cache.clone();
+            tokio::spawn(async move { cache.value_async(TestKey(1)).await })
+        })
+        .collect();
+    for task in tasks {
+        assert!(task.await.unwrap().is_ok());
+    }
+    assert_eq!(loads.load(Ordering::Relaxed), 1);
+    assert_eq!(cache.get_value(&TestKey(1)).map(|v| v.0), Some(1));
+    assert!(cache.value_async(TestKey(1)).await.is_ok());
+    assert_eq!(loads.load(Ordering::Relaxed), 1);
+}
+
+#[tokio::test]
+async fn value_async_blocking_loader() {
+    let mut cache = CacheTable::new("value_async_blocking_loader".into());
+    let (tx, rx) = std::sync::mpsc::channel();
+    let rx = std::sync::Mutex::new(rx);
+    // The loader blocks until a task on the same runtime thread has run.
+    cache.set_loader(move |_: &TestKey| {
+        let value = rx
+            .lock()
+            .unwrap()
+            .recv_timeout(Duration::from_secs(5))
+            .ok()?;
+        Some((TestValue(value), Duration::ZERO))
+    });
+    tokio::spawn(async move { tx.send(7).unwrap() });
+    let item = cache.value_async(TestKey(1)).await.unwrap();
+    assert_eq!(item.value().typed_ref::<TestValue>().map(|v| v.0), Some(7));
+}
+
+#[tokio::test]
+async fn fallible_loader() {
+    let mut cache = CacheTable::new("fallible_loader".into());
+    cache.set_try_data_loader(|key: TypedKey| match key.downcast_ref::<TestKey>() {
+        Some(TestKey(1)) => Err(std::io::Error::other("backend down")),
+        Some(TestKey(2)) => Ok(Some(CacheItem::new(
+            TestKey(2),
+            Duration::ZERO,
+            TestValue(2),
+        ))),
+        _ => Ok(None),
+    });
+    match cache.value(TestKey(1)) {
+        Err(Error::LoadFailed(e)) => assert_eq!(e.to_string(), "backend down"),
+        _ => unreachable!(),
+    }
+    assert!(cache.value(TestKey(2)).is_ok());
+    assert!(matches!(
+        cache.value(TestKey(3)),
+        Err(Error::KeyNotFoundOrLoadable)
+    ));
+
+    cache.set_try_async_data_loader(|_| {
+        Box::pin(async { Err::<Option<CacheItem>, _>("backend down") })
+    });
+    match cache.value_async(TestKey(4)).await {
+        Err(e @ Error::LoadFailed(_)) => assert!(std::error::Error::source(&e).is_some()),
+        _ => unreachable!(),
+    }
+}
+
+#[tokio::test]
+async fn type_loader() {
+    let mut cache = CacheTable::new("type_loader".into());
+    cache.set_data_loader(|key: TypedKey| {
+        let key = key.downcast::<BlobKey>().ok()?;
+        Some(CacheItem::new(key, Duration::ZERO, vec![0; 3]))
+    });
+    cache.set_loader(|key: &TestKey| Some((TestValue(key.0 * 2), Duration::ZERO)));
+    assert!(cache.value(TestKey(2)).is_ok());
+    assert_eq!(cache.get_value(&TestKey(2)).map(|v| v.0), Some(4));
+    assert!(cache.value_async(TestKey(3)).await.is_ok());
+    assert_eq!(cache.get_value(&TestKey(3)).map(|v| v.0), Some(6));
+    assert!(cache.value(BlobKey(1)).is_ok());
+    assert_eq!(cache.get_value(&BlobKey(1)).map(|v| v.len()), Some(3));
+}
+
+#[tokio::test]
+async fn batch_loader() {
+    let mut cache = CacheTable::new("batch_loader".into());
+    let batches = Arc::new(std::sync::Mutex::new(Vec::new()));
+    cache.set_batch_loader(Duration::ZERO, Duration::from_millis(20), {
+        let batches = batches.clone();
+        move |keys: Vec<TestKey>| {
+            batches.lock().unwrap().push(keys.len());
+            keys.into_iter()
+                .filter(|key| key.0 != 0)
+                .map(|key| (key.clone(), TestValue(key.0)))
+                .collect()
+        }
+    });
+    cache.add(TestKey(1), Duration::ZERO, TestValue(1));
+
+    let values = cache.get_many([TestKey(0), TestKey(1), TestKey(2), TestKey(3)]);
+    assert_eq!(values.len(), 3);
+    assert_eq!(values.get(&TestKey(3)).map(|v| v.0), Some(3));
+    assert_eq!(*batches.lock().unwrap(), vec![3]);
+
+    let tasks: Vec<_> = (4..7)
+        .map(|i| {
+            let cache = cache.clone();
+            tokio::spawn(async move { cache.get_many_async([TestKey(1), TestKey(i)]).await })
+        })
+        .collect();
+    for task in tasks {
+        assert_eq!(task.await.unwrap().len(), 2);
+    }
+    assert_eq!(*batches.lock().unwrap(), vec![3, 3]);
+}
+
+#[tokio::test]
+async fn refresh_after() {
+    use tokio_stream::StreamExt;
+
+    let clock = MockClock::new();
+    let loads = Arc::new(AtomicUsize::new(0));
+    let expired = Arc::new(AtomicUsize::new(0));
+    let gone = Arc::new(AtomicBool::new(false));
+    let mut cache = CacheTable::new("refresh_after".into());
+    cache.set_clock(clock.clone());
+    cache.set_refresh_after_for::<TestKey>(Duration::from_secs(30));
+    cache.set_loader({
+        let loads = loads.clone();
+        let gone = gone.clone();
+        move |key: &TestKey| {
+            let n = loads.fetch_add(1, Ordering::SeqCst) + 1;
+            (!gone.load(Ordering::SeqCst)).then(|| (TestValue(key.0 * 10 + n), Duration::ZERO))
+        }
+    });
+    let item = cache.value(TestKey(1)).unwrap();
+    {
+        let expired = expired.clone();
+        item.add_about_to_expire_callback(Box::new(move |_, cause| {
+            assert_eq!(cause, RemovalCause::Expired);
+            expired.fetch_add(1, Ordering::SeqCst);
+        }));
+    }
+    assert_eq!(cache.get_value(&TestKey(1)).map(|v| v.0), Some(11));
+
+    let mut events = cache.subscribe();
+    clock.advance(Duration::from_secs(30));
+    let stale = cache.value(TestKey(1)).unwrap();
+    assert_eq!(
+        stale.value().typed_ref::<TestValue>().map(|v| v.0),
+        Some(11)
+    );
+    assert!(matches!(
+        events.next().await.unwrap().unwrap(),
+        CacheEvent::Replaced { .. }
+    ));
+    assert_eq!(loads.load(Ordering::SeqCst), 2);
+    assert_eq!(cache.get_value(&TestKey(1)).map(|v| v.0), Some(12));
+    assert_eq!(
+        cache.get(&TestKey(1)).map(|item| item.access_count()),
+        Some(stale.access_count())
+    );
+
+    gone.store(true, Ordering::SeqCst);
+    clock.advance(Duration::from_secs(30));
+    assert!(cache.value(TestKey(1)).is_ok());
+    assert!(matches!(
+        events.next().await.unwrap().unwrap(),
+        CacheEvent::Removed(_, RemovalCause::Expired)
+    ));
+    assert!(!cache.exists(TestKey(1)));
+    assert_eq!(expired.load(Ordering::SeqCst), 1);
+    assert_eq!(cache.stats().deletions, 0);
+    assert_eq!(cache.stats().expirations, 1);
+}
+
+#[tokio::test]
+async fn stale() {
+    use tokio_stream::StreamExt;
+
+    let clock = MockClock::new();
+    let down = Arc::new(AtomicBool::new(true));
+    let mut cache = CacheTable::new("stale".into());
+    cache.set_clock(clock.clone());
+    cache.set_grace_period(Duration::from_secs(60));
+    cache.set_try_data_loader({
+        let down = down.clone();
+        move |key: TypedKey| {
+            if down.load(Ordering::SeqCst) {
+                return Err(std::io::Error::other("backend down"));
+            }
+            Ok(key.downcast::<TestKey>().ok().map(|key| {
+                let value = TestValue(key.0 * 100);
+                CacheItem::new(key, Duration::from_secs(10), value)
+            }))
+        }
+    });
+    cache.add(TestKey(1), Duration::from_secs(10), TestValue(1));
+    cache.add(TestKey(2), Duration::from_secs(10), TestValue(2));
+    clock.advance(Duration::from_secs(10));
+    cache.clean_up();
+    assert_eq!(cache.count(), 0);
+    assert_eq!(cache.usage::<TestKey>().count, 2);
+    assert_eq!(cache.stats_by_type()[&TypeId::of::<TestKey>()].count, 0);
+    assert!(matches!(cache.delete(&TestKey(2)), Err(Error::KeyNotFound)));
+    assert_eq!(cache.usage::<TestKey>().count, 1);
+    assert!(cache.get(&TestKey(1)).is_none());
+    assert!(matches!(cache.value(TestKey(1)), Err(Error::LoadFailed(_))));
+    let item = cache
+        .value_or_stale(TestKey(1), StaleMode::IfError)
+        .unwrap();
+    assert!(item.is_stale());
+    assert_eq!(item.value().typed_ref::<TestValue>().map(|v| v.0), Some(1));
+
+    down.store(false, Ordering::SeqCst);
+    let mut events = cache.subscribe();
+    let item = cache
+        .value_or_stale_async(TestKey(1), StaleMode::WhileRevalidate)
+        .await
+        .unwrap();
+    assert!(item.is_stale());
+    assert!(matches!(
+        events.next().await.unwrap().unwrap(),
+        CacheEvent::Replaced { .. }
+    ));
+    assert_eq!(cache.get_value(&TestKey(1)).map(|v| v.0), Some(100));
+
+    clock.advance(Duration::from_secs(60));
+    cache.clean_up();
+    assert_eq!(cache.count(), 0);
+    assert_eq!(cache.usage::<TestKey>().count, 1);
+    assert!(!cache.exists(TestKey(2)));
+}
+
+struct TenSeconds;
+
+impl Expiry<TestKey> for TenSeconds {
+    fn expire_after_create(&self, _: &TestKey, _: &TestValue, _: Instant) -> Option<Duration> {
+        Some(Duration::from_secs(10))
+    }
+}
+
+#[tokio::test]
+async fn stale_expiry() {
+    let clock = MockClock::new();
+    let mut cache = CacheTable::new("stale_expiry".into());
+    cache.set_clock(clock.clone());
+    cache.set_grace_period(Duration::from_secs(60));
+    cache.set_expiry(TenSeconds);
+    cache.set_data_loader(|key: TypedKey| {
+        key.downcast::<TestKey>().ok().map(|key| {
+            let value = TestValue(key.0 * 100);
+            CacheItem::new(key, Duration::ZERO, value)
+        })
+    });
+    cache.add(TestKey(1), Duration::ZERO, TestValue(1));
+    clock.advance(Duration::from_secs(10));
+    cache.clean_up();
+    assert!(cache.get(&TestKey(1)).is_none());
+    let item = cache
+        .value_or_stale(TestKey(1), StaleMode::IfError)
+        .unwrap();
+    assert!(!item.is_stale());
+    assert_eq!(
+        item.expires_at(),
+        Some(clock.now() + Duration::from_secs(10))
+    );
+}
+
+#[tokio::test]
+async fn stale_eviction() {
+    let clock = MockClock::new();
+    let mut cache = CacheTable::with_capacity("stale_eviction".into(), 2, Lru);
+    cache.set_clock(clock.clone());
+    cache.set_grace_period(Duration::from_secs(60));
+    cache.add(TestKey(1), Duration::ZERO, TestValue(1));
+    cache.add(TestKey(2), Duration::from_secs(10), TestValue(2));
+    clock.advance(Duration::from_secs(10));
+    cache.clean_up();
+    assert_eq!(cache.count(), 1);
+    cache.add(TestKey(3), Duration::ZERO, TestValue(3));
+    assert!(cache.exists(TestKey(1)));
+    assert_eq!(cache.usage::<TestKey>().count, 2);
+}
+
+#[tokio::test]
+async fn negative_time_to_live() {
+    let clock = MockClock::new();
+    let loads = Arc::new(AtomicUsize::new(0));
+    let mut cache = CacheTable::new("negative_time_to_live".into());
+    cache.set_clock(clock.clone());
+    cache.set_negative_time_to_live(Duration::from_secs(30));
+    cache.set_loader({
+        let loads = loads.clone();
+        move |_: &TestKey| -> Option<(TestValue, Duration)> {
+            loads.fetch_add(1, Ordering::SeqCst);
+            None
+        }
+    });
+    for _ in 0..3 {
+        assert!(matches!(
+            cache.value(TestKey(1)),
+            Err(Error::KeyNotFoundOrLoadable)
+        ));
+        assert!(matches!(
+            cache.value_async(TestKey(1)).await,
+            Err(Error::KeyNotFoundOrLoadable)
+        ));
+    }
+    assert_eq!(loads.load(Ordering::SeqCst), 1);
+
+    clock.advance(Duration::from_secs(30));
+    assert!(cache.value(TestKey(1)).is_err());
+    assert_eq!(loads.load(Ordering::SeqCst), 2);
+
+    cache.add(TestKey(1), Duration::ZERO, TestValue(1));
+    cache.delete(&TestKey(1)).unwrap();
+    assert!(cache.value(TestKey(1)).is_err());
+    assert_eq!(loads.load(Ordering::SeqCst), 3);
+
+    assert!(cache.value(TestKey(2)).is_err());
+    assert_eq!(
+        cache.clean_up(),
+        Some(clock.now() + Duration::from_secs(30))
+    );
+    // Expired misses are dropped, so they no longer hold up a clean up.
+    clock.advance(Duration::from_secs(30));
+    assert_eq!(cache.clean_up(), None);
+}
+
+#[tokio::test]
+async fn stats() {
+    let clock = MockClock::new();
+    let mut cache = CacheTable::with_capacity("stats".into(), 2, Lru);
+    cache.set_clock(clock.clone());
+    cache.set_try_data_loader(|key: TypedKey| match key.downcast::<TestKey>() {
+        Ok(TestKey(0)) => Err(std::io::Error::other("backend down")),
+        Ok(key) => {
+            let value = TestValue(key.0);
+            Ok(Some(CacheItem::new(key, Duration::from_secs(10), value)))
+        }
+        Err(_) => Ok(None),
+    });
+    cache.add(TestKey(1), Duration::ZERO, TestValue(1));
+    assert!(cache.value(TestKey(1)).is_ok());
+    assert!(cache.value(TestKey(2)).is_ok());
+    assert!(cache.value(TestKey(0)).is_err());
+    assert!(cache.value_async(TestKey(3)).await.is_ok());
+    cache.delete(&TestKey(1)).unwrap();
+    clock.advance(Duration::from_secs(10));
+    cache.clean_up();
+
+    let stats = cache.stats();
+    assert_eq!(
+        stats,
+        Stats {
+            total_load_time: stats.total_load_time,
+            hits: 1,
+            misses: 3,
+            loads: 3,
+            load_failures: 1,
+            insertions: 3,
+            deletions: 1,
+            expirations: 1,
+            evictions: 1,
+        }
+    );
+    assert_eq!(stats.hit_rate(), 0.25);
+    cache.reset_stats();
+    assert_eq!(cache.stats(), Stats::default());
+
+    cache.add(TestKey(4), Duration::ZERO, TestValue(4));
+    assert!(cache.get(&TestKey(4)).is_some());
+    assert!(cache.get_value(&TestKey(4)).is_some());
+    assert!(cache.exists(TestKey(4)));
+    assert!(cache.get_value_arc(&TestKey(5)).is_none());
+    assert!(cache.update(&TestKey(4), |v| v.0 += 1).is_ok());
+    let stats = cache.stats();
+    assert_eq!((stats.hits, stats.misses), (2, 1));
+}
+
+#[tokio::test]
+async fn stats_by_type() {
+    let clock = MockClock::new();
+    let mut cache = CacheTable::new("stats_by_type".into());
+    cache.set_clock(clock.clone());
+    cache.add(TestKey(1), Duration::ZERO, TestValue(1));
+    cache.add(TestKey(2), Duration::ZERO, TestValue(2));
+    cache.add(BlobKey(1), Duration::from_secs(10), vec![0; 3]);
+    assert!(cache.value(TestKey(1)).is_ok());
+    assert!(cache.value(TestKey(3)).is_err());
+    assert!(cache.value(BlobKey(1)).is_ok());
+    clock.advance(Duration::from_secs(20));
+    cache.clean_up();
+
+    let stats = cache.stats_by_type();
+    assert_eq!(
+        stats[&TypeId::of::<TestKey>()],
+        TypeStats {
+            type_name: std::any::type_name::<TestKey>(),
+            count: 2,
+            hits: 1,
+            misses: 1,
+            expirations: 0,
+        }
+    );
+    assert_eq!(
+        stats[&TypeId::of::<BlobKey>()],
+        TypeStats {
+            type_name: "test::BlobKey",
+            count: 0,
+            hits: 1,
+            misses: 0,
+            expirations: 1,
+        }
+    );
+}
+
+#[tokio::test]
+async fn metrics() {
+    let cache = CacheTable::new("metrics \"a\"".into());
+    cache.add(TestKey(1), Duration::ZERO, TestValue(1));
+    cache.add(BlobKey(1), Duration::ZERO, vec![0; 3]);
+    assert!(cache.value(TestKey(1)).is_ok());
+    assert!(cache.value(TestKey(2)).is_err());
+    let other = CacheTable::new("metrics_b".into());
+    assert_eq!(
+        typedcache::metrics::render(&[other, cache]),
+        r#"# HELP typedcache_items Number of items stored in the table.
+# TYPE typedcache_items gauge
+typedcache_items{table="metrics \"a\""} 2
+typedcache_items{table="metrics_b"} 0
+# HELP typedcache_weight Total weight of the items stored in the table.
+# TYPE typedcache_weight gauge
+typedcache_weight{table="metrics \"a\""} 2
+typedcache_weight{table="metrics_b"} 0
+# HELP typedcache_hits_total Number of lookups which found an item.
+# TYPE typedcache_hits_total counter
+typedcache_hits_total{table="metrics \"a\""} 1
+typedcache_hits_total{table="metrics_b"} 0
+# HELP typedcache_misses_total Number of lookups which did not find an item.
+# TYPE typedcache_misses_total counter
+typedcache_misses_total{table="metrics \"a\""} 1
+typedcache_misses_total{table="metrics_b"} 0
+# HELP typedcache_loads_total Number of calls to a data-loader.
+# TYPE typedcache_loads_total counter
+typedcache_loads_total{table="metrics \"a\""} 0
+typedcache_loads_total{table="metrics_b"} 0
+# HELP typedcache_load_failures_total Number of calls to a data-loader which returned an error.
+# TYPE typedcache_load_failures_total counter
+typedcache_load_failures_total{table="metrics \"a\""} 0
+typedcache_load_failures_total{table="metrics_b"} 0
+# HELP typedcache_load_seconds_total Total time spent in data-loaders.
+# TYPE typedcache_load_seconds_total counter
+typedcache_load_seconds_total{table="metrics \"a\""} 0
+typedcache_load_seconds_total{table="metrics_b"} 0
+# HELP typedcache_insertions_total Number of items added to the table.
+# TYPE typedcache_insertions_total counter
+typedcache_insertions_total{table="metrics \"a\""} 2
+typedcache_insertions_total{table="metrics_b"} 0
+# HELP typedcache_deletions_total Number of items explicitly deleted from the table.
+# TYPE typedcache_deletions_total counter
+typedcache_deletions_total{table="metrics \"a\""} 0
+typedcache_deletions_total{table="metrics_b"} 0
+# HELP typedcache_expirations_total Number of items removed from the table after they expired.
+# TYPE typedcache_expirations_total counter
+typedcache_expirations_total{table="metrics \"a\""} 0
+typedcache_expirations_total{table="metrics_b"} 0
+# HELP typedcache_evictions_total Number of items evicted from the table.
+# TYPE typedcache_evictions_total counter
+typedcache_evictions_total{table="metrics \"a\""} 0
+typedcache_evictions_total{table="metrics_b"} 0
+# HELP typedcache_key_type_items Number of items of a key type stored in the table.
+# TYPE typedcache_key_type_items gauge
+typedcache_key_type_items{table="metrics \"a\"",key_type="test::BlobKey"} 1
+typedcache_key_type_items{table="metrics \"a\"",key_type="test::TestKey"} 1
+# HELP typedcache_key_type_hits_total Number of lookups of a key type which found an item.
+# TYPE typedcache_key_type_hits_total counter
+typedcache_key_type_hits_total{table="metrics \"a\"",key_type="test::BlobKey"} 0
+typedcache_key_type_hits_total{table="metrics \"a\"",key_type="test::TestKey"} 1
+# HELP typedcache_key_type_misses_total Number of lookups of a key type which did not find an item.
+# TYPE typedcache_key_type_misses_total counter
+typedcache_key_type_misses_total{table="metrics \"a\"",key_type="test::BlobKey"} 0
+typedcache_key_type_misses_total{table="metrics \"a\"",key_type="test::TestKey"} 1
+# HELP typedcache_key_type_expirations_total Number of items of a key type removed from the table after they expired.
+# TYPE typedcache_key_type_expirations_total counter
+typedcache_key_type_expirations_total{table="metrics \"a\"",key_type="test::BlobKey"} 0
+typedcache_key_type_expirations_total{table="metrics \"a\"",key_type="test::TestKey"} 0
+"#
+    );
+
+    typedcache::cache("metrics_registered".into());
+    assert!(typedcache::metrics::render_all()
+        .contains("typedcache_items{table=\"metrics_registered\"} 0\n"));
+}
+
+#[tokio::test]
+async fn removal_cause() {
+    let clock = MockClock::new();
+    let mut cache = CacheTable::with_capacity("removal_cause".into(), 3, Fifo);
+    cache.set_clock(clock.clone());
+    let causes = Arc::new(std::sync::Mutex::new(Vec::new()));
+    cache.add_about_to_delete_item_callback({
+        let causes = causes.clone();
+        move |item, cause| {
+            let key = item.key().downcast_ref::<TestKey>().unwrap().0;
+            causes.lock().unwrap().push((key, cause));
+        }
+    });
+    let expired = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let item = cache.add(TestKey(1), Duration::from_secs(10), TestValue(1));
+    assert!(item.is_none());
+    cache
+        .get(&TestKey(1))
+        .unwrap()
+        .add_about_to_expire_callback(Box::new({
+            let expired = expired.clone();
+            move |_, cause| expired.lock().unwrap().push(cause)
+        }));
+    clock.advance(Duration::from_secs(1));
+    cache.add(TestKey(2), Duration::ZERO, TestValue(2));
+    cache.add(TestKey(2), Duration::ZERO, TestValue(20));
+    cache.add(TestKey(3), Duration::ZERO, TestValue(3));
+    cache.delete(&TestKey(3)).unwrap();
+    clock.advance(Duration::from_secs(1));
+    cache.add(TestKey(4), Duration::ZERO, TestValue(4));
+    cache.add(TestKey(5), Duration::ZERO, TestValue(5));
+    clock.advance(Duration::from_secs(1));
+    cache.add(TestKey(6), Duration::ZERO, TestValue(6));
+    cache.flush();
+
+    let mut causes = causes.lock().unwrap().clone();
+    causes[4..].sort_by_key(|(key, _)| *key);
+    assert_eq!(
+        causes,
+        vec![
+            (2, RemovalCause::Replaced),
+            (3, RemovalCause::Explicit),
+            (1, RemovalCause::Evicted),
+            (2, RemovalCause::Evicted),
+            (4, RemovalCause::Flushed),
+            (5, RemovalCause::Flushed),
+            (6, RemovalCause::Flushed),
+        ]
+    );
+    assert_eq!(*expired.lock().unwrap(), vec![RemovalCause::Evicted]);
+}
+
+#[tokio::test]
+async fn reentrant_callbacks() {
+    let clock = MockClock::new();
+    let mut cache = CacheTable::new("reentrant_callbacks".into());
+    cache.set_clock(clock.clone());
+    cache.add_added_item_callback({
+        let cache = cache.clone();
+        move |item| {
+            if let Some(key) = item.key().downcast_ref::<TestKey>() {
+                assert!(cache.exists(key.clone()));
+            }
+        }
+    });
+    cache.add_about_to_delete_item_callback({
+        let cache = cache.clone();
+        move |item, _| {
+            if let Some(key) = item.key().downcast_ref::<TestKey>() {
+                assert!(cache.get(key).is_none());
+                cache.add(BlobKey(key.0), Duration::ZERO, Vec::new());
+            }
+        }
+    });
+    cache.add(TestKey(1), Duration::ZERO, TestValue(1));
+    cache.add(TestKey(2), Duration::from_secs(10), TestValue(2));
+    cache.add(TestKey(3), Duration::ZERO, TestValue(3));
+    cache
+        .get(&TestKey(2))
+        .unwrap()
+        .add_about_to_expire_callback(Box::new({
+            let cache = cache.clone();
+            move |_, _| {
+                cache.delete(&TestKey(3)).unwrap();
+            }
+        }));
+
+    cache.delete(&TestKey(1)).unwrap();
+    assert!(cache.exists(BlobKey(1)));
+    clock.advance(Duration::from_secs(10));
+    cache.clean_up();
+    assert!(cache.exists(BlobKey(2)));
+    assert!(cache.exists(BlobKey(3)));
+    assert_eq!(cache.count(), 3);
+}
+
+#[tokio::test]
+async fn async_callbacks() {
+    let mut cache = CacheTable::new("async_callbacks".into());
+    let events = Arc::new(std::sync::Mutex::new(Vec::new()));
+    cache.add_added_item_callback_async({
+        let events = events.clone();
+        move |item| {
+            let events = events.clone();
+            async move {
+                let key = item.key().downcast_ref::<TestKey>().unwrap().0;
+                tokio::time::sleep(Duration::from_millis(30 / key as u64)).await;
+                events.lock().unwrap().push((key, None));
+            }
+        }
+    });
+    cache.add_about_to_delete_item_callback_async({
+        let events = events.clone();
+        move |item, cause| {
+            let events = events.clone();
+            async move {
+                let key = item.key().downcast_ref::<TestKey>().unwrap().0;
+                events.lock().unwrap().push((key, Some(cause)));
+            }
+        }
+    });
+    cache.add(TestKey(1), Duration::ZERO, TestValue(1));
+    cache.add(TestKey(2), Duration::ZERO, TestValue(2));
+    cache.delete(&TestKey(1)).unwrap();
+    cache.wait_for_notifications().await;
+    assert_eq!(
+        *events.lock().unwrap(),
+        vec![(1, None), (2, None), (1, Some(RemovalCause::Explicit))]
+    );
+
+    cache.remove_added_item_callbacks();
+    cache.remove_about_to_delete_item_callbacks();
+    cache.set_notification_concurrency(2);
+    let running = Arc::new(AtomicUsize::new(0));
+    let max_running = Arc::new(AtomicUsize::new(0));
+    cache.add_added_item_callback_async({
+        let running = running.clone();
+        let max_running = max_running.clone();
+        move |_| {
+            let running = running.clone();
+            let max_running = max_running.clone();
+            async move {
+                let n = running.fetch_add(1, Ordering::SeqCst) + 1;
+                max_running.fetch_max(n, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(10)).await;
+                running.fetch_sub(1, Ordering::SeqCst);
+            }
+        }
+    });
+    for i in 3..9 {
+        cache.add(TestKey(i), Duration::ZERO, TestValue(i));
+    }
+    cache.wait_for_notifications().await;
+    assert_eq!(running.load(Ordering::SeqCst), 0);
+    assert_eq!(max_running.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn subscribe() {
+    use tokio_stream::StreamExt;
+
+    let cache = CacheTable::new("subscribe".into());
+    let mut audit = cache.subscribe();
+    cache.add(TestKey(1), Duration::ZERO, TestValue(1));
+    let mut replication = cache.subscribe();
+    cache.add(TestKey(1), Duration::ZERO, TestValue(2));
+    cache.delete(&TestKey(1)).unwrap();
+    cache.add(TestKey(2), Duration::ZERO, TestValue(2));
+    cache.flush();
+
+    let describe = |event: CacheEvent| {
+        let key = event
+            .key()
+            .and_then(|key| key.downcast_ref::<TestKey>())
+            .map(|key| key.0);
+        match event {
+            CacheEvent::Added(_) => format!("added {key:?}"),
+            CacheEvent::Replaced { old, .. } => format!(
+                "replaced {key:?} from {:?}",
+                old.value().typed_ref::<TestValue>().map(|v| v.0)
+            ),
+            CacheEvent::Removed(_, cause) => format!("removed {key:?} {cause:?}"),
+            CacheEvent::Flushed => "flushed".to_string(),
+        }
+    };
+    let mut events = Vec::new();
+    for _ in 0..5 {
+        events.push(describe(audit.next().await.unwrap().unwrap()));
+    }
+    assert_eq!(
+        events,
+        [
+            "added Some(1)",
+            "replaced Some(1) from Some(1)",
+            "removed Some(1) Explicit",
+            "added Some(2)",
+            "flushed"
+        ]
+    );
+    let mut events = Vec::new();
+    for _ in 0..4 {
+        events.push(describe(replication.next().await.unwrap().unwrap()));
+    }
+    assert_eq!(
+        events,
+        [
+            "replaced Some(1) from Some(1)",
+            "removed Some(1) Explicit",
+            "added Some(2)",
+            "flushed"
+        ]
+    );
+}
+
+#[tokio::test]
+async fn typed_callbacks() {
+    let mut cache = CacheTable::new("typed_callbacks".into());
+    let added = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let removed = Arc::new(std::sync::Mutex::new(Vec::new()));
+    cache.on_added({
+        let added = added.clone();
+        move |key: &TestKey, value: &TestValue| added.lock().unwrap().push((key.0, value.0))
+    });
+    cache.on_removed({
+        let removed = removed.clone();
+        move |key: &BlobKey, value: &Vec<u8>, cause| {
+            removed.lock().unwrap().push((key.0, value.len(), cause))
+        }
+    });
+    cache.add(TestKey(1), Duration::ZERO, TestValue(10));
+    cache.add(BlobKey(2), Duration::ZERO, vec![0; 3]);
+    cache.delete(&TestKey(1)).unwrap();
+    cache.delete(&BlobKey(2)).unwrap();
+    assert_eq!(*added.lock().unwrap(), vec![(1, 10)]);
+    assert_eq!(
+        *removed.lock().unwrap(),
+        vec![(2, 3, RemovalCause::Explicit)]
+    );
+}